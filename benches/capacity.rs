@@ -0,0 +1,69 @@
+//! Counts the allocations made while parsing a deeply nested document,
+//! with and without the capacity hints of the builder.
+//!
+//! Run with `cargo bench --bench capacity`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use r_json::{Event, JsonParserBuilder};
+
+const DEPTH: usize = 120;
+const ROUNDS: u32 = 1000;
+
+/// Counts allocations and reallocations, which is what the hints avoid.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn run(name: &str, data: &[u8], builder: impl Fn() -> JsonParserBuilder<'static>) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut count = 0;
+        builder()
+            .build(data, |e: &Event| {
+                count += e.path.len();
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        black_box(count);
+    }
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / ROUNDS as f64;
+    println!("{:<16} {:>8.1} allocations, {:>10.3?} per document", name, allocations, start.elapsed() / ROUNDS);
+}
+
+fn main() {
+    let mut data = String::new();
+    for i in 0..DEPTH {
+        data.push_str(&format!("{{\"level_{}\": ", i));
+    }
+    data.push_str("\"a value that is a little longer than the rest\"");
+    data.push_str(&"}".repeat(DEPTH));
+
+    run("no hints", data.as_bytes(), JsonParserBuilder::new);
+    run("with hints", data.as_bytes(), || JsonParserBuilder::new().expected_depth(DEPTH).value_capacity(64));
+}
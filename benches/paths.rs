@@ -0,0 +1,81 @@
+//! Times parsing a large array, where every element gets an index pushed
+//! onto the path, and the two ways of formatting that index.
+//!
+//! Run with `cargo bench --bench paths`.
+
+use std::fmt::Write;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use r_json::json_parse;
+
+const ELEMENTS: usize = 100_000;
+const ROUNDS: u32 = 20;
+
+fn time(name: &str, mut f: impl FnMut()) {
+    f();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let per_round = start.elapsed() / ROUNDS;
+    println!("{:<24} {:>10.3?} per round, {:>6.1} ns per element", name, per_round, ns_per_element(per_round));
+}
+
+fn ns_per_element(d: Duration) -> f64 {
+    d.as_nanos() as f64 / ELEMENTS as f64
+}
+
+/// The same as `push_index` in the parser, which is private.
+fn push_index(path: &mut String, mut i: usize) {
+    let mut digits = [0; 20];
+    let mut n = digits.len();
+    loop {
+        n -= 1;
+        digits[n] = b'0' + (i % 10) as u8;
+        i /= 10;
+        if i == 0 {
+            break;
+        }
+    }
+    path.push('[');
+    path.push_str(std::str::from_utf8(&digits[n..]).unwrap());
+    path.push(']');
+}
+
+fn main() {
+    let data = format!("[{}]", (0..ELEMENTS).map(|i| i.to_string()).collect::<Vec<_>>().join(","));
+
+    time("parse array", || {
+        let mut count = 0;
+        json_parse(data.as_bytes(), |p, _, _| count += p.len()).unwrap();
+        black_box(count);
+    });
+
+    let mut path = String::from("$");
+    time("index with to_string", || {
+        for i in 0..ELEMENTS {
+            path.truncate(1);
+            path.push('[');
+            path.push_str(&black_box(i).to_string());
+            path.push(']');
+        }
+        black_box(&path);
+    });
+
+    time("index with write!", || {
+        for i in 0..ELEMENTS {
+            path.truncate(1);
+            write!(path, "[{}]", black_box(i)).unwrap();
+        }
+        black_box(&path);
+    });
+
+    time("index with push_index", || {
+        for i in 0..ELEMENTS {
+            path.truncate(1);
+            push_index(&mut path, black_box(i));
+        }
+        black_box(&path);
+    });
+}
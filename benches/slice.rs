@@ -0,0 +1,45 @@
+//! Times parsing a document held in memory through the `Read` interface
+//! and directly from the slice.
+//!
+//! Run with `cargo bench --bench slice`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use r_json::{json_parse, json_parse_slice};
+
+const RECORDS: usize = 20_000;
+const ROUNDS: u32 = 20;
+
+fn time(name: &str, size: usize, mut f: impl FnMut()) {
+    f();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let per_round = start.elapsed() / ROUNDS;
+    println!("{:<20} {:>10.3?} per round, {:>7.1} MB/s", name, per_round, mb_per_second(size, per_round));
+}
+
+fn mb_per_second(size: usize, d: Duration) -> f64 {
+    size as f64 / d.as_secs_f64() / 1e6
+}
+
+fn main() {
+    let records = (0..RECORDS)
+        .map(|i| format!(r#"{{"id": {}, "name": "user number {}", "email": "user{}@example.com", "note": "a \"quoted\" note", "active": true}}"#, i, i, i))
+        .collect::<Vec<_>>();
+    let data = format!("[{}]", records.join(","));
+
+    time("read", data.len(), || {
+        let mut count = 0;
+        json_parse(data.as_bytes(), |_, _, v| count += v.len()).unwrap();
+        black_box(count);
+    });
+
+    time("slice", data.len(), || {
+        let mut count = 0;
+        json_parse_slice(data.as_bytes(), |_, _, v| count += v.len()).unwrap();
+        black_box(count);
+    });
+}
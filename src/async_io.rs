@@ -0,0 +1,120 @@
+//! Parsing from a `tokio::io::AsyncRead`, enabled by the `async` feature.
+//!
+//! The bytes are handed to an incremental parser as they are read, see
+//! [`JsonParser::feed`].
+
+use std::ops::ControlFlow;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{Event, Feed, JsonError, JsonParser, JsonType, READ_BUFFER_SIZE};
+
+/// Parses a document from an async reader, reporting values like
+/// [`json_parse`](crate::json_parse).
+///
+/// The reader is only read once the parser has used up everything read
+/// before, and the callback runs between reads, so a slow callback slows
+/// down reading and applies backpressure to the sender. Each read carries
+/// on where the last one stopped, even in the middle of a string, so the
+/// input is parsed once however it is split. Besides the read buffer, only
+/// the decoded part of the current string is held.
+pub async fn json_parse_async<T, F>(mut reader: T, mut on_value: F) -> Result<(), JsonError>
+where
+    T: AsyncRead + Unpin,
+    F: FnMut(&str, JsonType, &str)
+{
+    let mut parser = JsonParser::new(Feed::default(), |e: &Event| {
+        on_value(e.path, e.typ, e.value);
+        ControlFlow::Continue(())
+    });
+    let mut buf = vec![0; READ_BUFFER_SIZE];
+
+    loop {
+        let n = reader.read(&mut buf).await.map_err(|error| JsonError::Io { error, pos: parser.pos() })?;
+        if n == 0 {
+            return parser.close();
+        }
+        parser.feed(&buf[..n])?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::ReadBuf;
+
+    use super::*;
+    use crate::json_parse;
+
+    /// Returns three bytes at a time, and `Pending` before every read.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        ready: bool,
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            if !self.ready {
+                self.ready = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let n = buf.remaining().min(self.data.len()).min(3);
+            buf.put_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            self.ready = false;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn block_on<R>(f: impl std::future::Future<Output = R>) -> R {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(f)
+    }
+
+    fn events_async(data: &[u8]) -> Result<Vec<String>, JsonError> {
+        let mut out = Vec::new();
+        block_on(json_parse_async(Trickle { data, ready: false }, |p, t, v| out.push(format!("{} {:?} {}", p, t, v))))?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_parse_async() {
+        let data = br#" {"key": "a longer \u00e9 string", "n": [1.5, -20, true, null], "o": {"": {}}, "long_number": 123456789}"#;
+        let mut expected = Vec::new();
+        json_parse(data.as_slice(), |p, t, v| expected.push(format!("{} {:?} {}", p, t, v))).unwrap();
+        assert_eq!(expected, events_async(data).unwrap());
+
+        let mut out = Vec::new();
+        block_on(json_parse_async(data.as_slice(), |p, _, _| out.push(p.to_string()))).unwrap();
+        assert_eq!(expected.len(), out.len());
+    }
+
+    #[test]
+    fn test_parse_async_large() {
+        let data = format!("[{}]", (0..20_000).map(|i| format!("\"item {}\"", i)).collect::<Vec<_>>().join(","));
+        let mut count = 0;
+        block_on(json_parse_async(data.as_bytes(), |_, _, _| count += 1)).unwrap();
+        assert_eq!(20_001, count);
+    }
+
+    #[test]
+    fn test_parse_async_long_string() {
+        let text = "abc\\u00e9 ".repeat(50_000);
+        let out = events_async(format!("[\"{}\"]", text).as_bytes()).unwrap();
+        assert_eq!(vec![format!("$[0] String {}", "abc\u{e9} ".repeat(50_000)), "$ Array ".to_string()], out);
+    }
+
+    #[test]
+    fn test_parse_async_errors() {
+        assert!(matches!(events_async(b"[1, 2"), Err(JsonError::UnexpectedEof { .. })));
+        assert!(matches!(events_async(b"[1] 2"), Err(JsonError::TrailingData { .. })));
+        assert!(matches!(events_async(b"\"abc"), Err(JsonError::UnterminatedString { .. })));
+        assert!(matches!(events_async(b""), Err(JsonError::UnexpectedEof { .. })));
+
+        let err = events_async(b"\n\n  [1, x]").unwrap_err();
+        assert_eq!((3, 7), (err.position().line, err.position().column));
+    }
+}
@@ -0,0 +1,365 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use crate::{ChunkHook, CompleteHook, Event, JsonParser, KeyHook, NumberHook, PathHook, DEFAULT_MAX_DEPTH};
+
+/// The settings a [`JsonParser`] is created with.
+#[derive(Debug, Clone)]
+pub(crate) struct Options {
+    pub(crate) container_events: bool,
+    pub(crate) max_depth: usize,
+    pub(crate) reject_duplicate_keys: bool,
+    pub(crate) allow_comments: bool,
+    pub(crate) lenient_whitespace: bool,
+    pub(crate) lossy_strings: bool,
+    pub(crate) raw_strings: bool,
+    pub(crate) allow_nan: bool,
+    pub(crate) allow_nul_escape: bool,
+    pub(crate) single_quotes: bool,
+    pub(crate) number_underscores: bool,
+    pub(crate) strict_trailing: bool,
+    pub(crate) max_string_len: Option<usize>,
+    pub(crate) max_value_len: Option<usize>,
+    pub(crate) max_total_bytes: Option<usize>,
+    pub(crate) max_array_elements: Option<usize>,
+    pub(crate) max_object_members: Option<usize>,
+    pub(crate) root: String,
+    pub(crate) separator: char,
+    pub(crate) lowercase_keys: bool,
+    pub(crate) path_segments: bool,
+    pub(crate) filter: Option<String>,
+    pub(crate) subtree: Option<String>,
+    pub(crate) expected_depth: usize,
+    pub(crate) value_capacity: usize,
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            container_events: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            reject_duplicate_keys: false,
+            allow_comments: false,
+            lenient_whitespace: false,
+            lossy_strings: false,
+            raw_strings: false,
+            allow_nan: false,
+            allow_nul_escape: false,
+            single_quotes: false,
+            number_underscores: false,
+            strict_trailing: true,
+            max_string_len: None,
+            max_value_len: None,
+            max_total_bytes: None,
+            max_array_elements: None,
+            max_object_members: None,
+            root: String::from("$"),
+            separator: '.',
+            lowercase_keys: false,
+            path_segments: false,
+            filter: None,
+            subtree: None,
+            expected_depth: 0,
+            value_capacity: 0,
+            cancel: None,
+        }
+    }
+}
+
+/// The optional callbacks a [`JsonParser`] is created with.
+#[derive(Default)]
+pub(crate) struct Hooks<'a> {
+    pub(crate) on_key: Option<KeyHook<'a>>,
+    /// The chunk size and the callback for string chunks.
+    pub(crate) on_chunk: Option<(usize, ChunkHook<'a>)>,
+    pub(crate) on_number_text: Option<NumberHook<'a>>,
+    pub(crate) on_complete: Option<CompleteHook<'a>>,
+    /// Callbacks for values at exact paths.
+    pub(crate) on_path: HashMap<String, Vec<PathHook<'a>>>,
+}
+
+/// Configures and creates a [`JsonParser`].
+#[derive(Default)]
+pub struct JsonParserBuilder<'a> {
+    options: Options,
+    hooks: Hooks<'a>,
+}
+
+impl<'a> JsonParserBuilder<'a> {
+    /// Starts with the defaults used by [`JsonParser::new`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports `ObjectStart`/`ObjectEnd` and `ArrayStart`/`ArrayEnd` as
+    /// containers are entered and left, instead of a single `Object` or
+    /// `Array` after the container has been read.
+    pub fn container_events(mut self, enabled: bool) -> Self {
+        self.options.container_events = enabled;
+        self
+    }
+
+    /// Limits how deeply arrays and objects may be nested, 128 by default.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.options.max_depth = depth;
+        self
+    }
+
+    /// Fails with [`JsonError::DuplicateKey`](crate::JsonError::DuplicateKey)
+    /// when an object has the same key more than once. Off by default.
+    pub fn reject_duplicate_keys(mut self, enabled: bool) -> Self {
+        self.options.reject_duplicate_keys = enabled;
+        self
+    }
+
+    /// Accepts `//` line comments and `/* */` block comments, which may be
+    /// nested, wherever whitespace is allowed. Off by default.
+    pub fn allow_comments(mut self, enabled: bool) -> Self {
+        self.options.allow_comments = enabled;
+        self
+    }
+
+    /// Accepts form feeds and vertical tabs as whitespace between tokens.
+    /// Off by default.
+    pub fn lenient_whitespace(mut self, enabled: bool) -> Self {
+        self.options.lenient_whitespace = enabled;
+        self
+    }
+
+    /// Replaces invalid escapes and invalid UTF-8 in strings and keys with
+    /// U+FFFD instead of failing. Off by default.
+    pub fn lossy_strings(mut self, enabled: bool) -> Self {
+        self.options.lossy_strings = enabled;
+        self
+    }
+
+    /// Reports string values as they are written between the quotes, with
+    /// escape sequences left as they are, so that `"a\nb"` has the value
+    /// `a\nb`. Escapes are not checked, but the text must still be valid
+    /// UTF-8. Keys are decoded as usual. Off by default.
+    pub fn raw_strings(mut self, enabled: bool) -> Self {
+        self.options.raw_strings = enabled;
+        self
+    }
+
+    /// Accepts the non-standard `NaN`, `Infinity` and `-Infinity` and
+    /// reports them as `Number`, which [`parse_number`](crate::parse_number)
+    /// understands. Off by default.
+    pub fn allow_nan(mut self, enabled: bool) -> Self {
+        self.options.allow_nan = enabled;
+        self
+    }
+
+    /// Accepts the non-standard escape `\0` in strings and keys and decodes
+    /// it to a NUL character. Off by default.
+    pub fn allow_nul_escape(mut self, enabled: bool) -> Self {
+        self.options.allow_nul_escape = enabled;
+        self
+    }
+
+    /// Accepts the non-standard single-quoted strings, as in `{'a': 'b'}`,
+    /// for both keys and values. Inside of them `\'` is an escaped quote
+    /// and `"` needs no escaping. Off by default.
+    pub fn single_quotes(mut self, enabled: bool) -> Self {
+        self.options.single_quotes = enabled;
+        self
+    }
+
+    /// Accepts underscores between the digits of numbers, as in
+    /// `1_000_000`, and leaves them out of the reported value. Off by
+    /// default.
+    pub fn number_underscores(mut self, enabled: bool) -> Self {
+        self.options.number_underscores = enabled;
+        self
+    }
+
+    /// Fails when anything but whitespace follows the top-level value. On
+    /// by default; when off, parsing stops after the value and the rest of
+    /// the input is left unread.
+    pub fn strict_trailing(mut self, enabled: bool) -> Self {
+        self.options.strict_trailing = enabled;
+        self
+    }
+
+    /// Fails with [`JsonError::StringTooLong`](crate::JsonError::StringTooLong)
+    /// once a key or string value decodes to more than `len` bytes.
+    /// Unlimited by default.
+    pub fn max_string_len(mut self, len: usize) -> Self {
+        self.options.max_string_len = Some(len);
+        self
+    }
+
+    /// Fails with [`JsonError::ValueTooLong`](crate::JsonError::ValueTooLong)
+    /// once a string value decodes to, or a number is written with, more
+    /// than `len` bytes. Unlike [`max_string_len`](Self::max_string_len),
+    /// keys are not limited. Unlimited by default.
+    pub fn max_value_len(mut self, len: usize) -> Self {
+        self.options.max_value_len = Some(len);
+        self
+    }
+
+    /// Fails with [`JsonError::TooManyElements`](crate::JsonError::TooManyElements)
+    /// at the path of an array once it has more than `len` elements.
+    /// Unlimited by default.
+    pub fn max_array_elements(mut self, len: usize) -> Self {
+        self.options.max_array_elements = Some(len);
+        self
+    }
+
+    /// Fails with [`JsonError::TooManyMembers`](crate::JsonError::TooManyMembers)
+    /// at the path of an object once it has more than `len` members.
+    /// Unlimited by default.
+    pub fn max_object_members(mut self, len: usize) -> Self {
+        self.options.max_object_members = Some(len);
+        self
+    }
+
+    /// Fails with [`JsonError::InputTooLarge`](crate::JsonError::InputTooLarge)
+    /// once more than `len` bytes of input have been read, whitespace
+    /// included. Unlimited by default.
+    pub fn max_total_bytes(mut self, len: usize) -> Self {
+        self.options.max_total_bytes = Some(len);
+        self
+    }
+
+    /// Reserves room up front for paths and open containers `depth` levels
+    /// deep, so that deep documents do not grow them bit by bit.
+    pub fn expected_depth(mut self, depth: usize) -> Self {
+        self.options.expected_depth = depth;
+        self
+    }
+
+    /// Reserves room up front for string and number values of `len` bytes.
+    pub fn value_capacity(mut self, len: usize) -> Self {
+        self.options.value_capacity = len;
+        self
+    }
+
+    /// Fails with [`JsonError::Cancelled`](crate::JsonError::Cancelled) once
+    /// `flag` has been set, for example by another thread. The flag is
+    /// looked at every few kilobytes of input.
+    pub fn cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.options.cancel = Some(flag);
+        self
+    }
+
+    /// Starts every path with `root` instead of `$`, such as `$.data` for
+    /// a document that was taken from that place in a larger one.
+    pub fn root(mut self, root: &str) -> Self {
+        self.options.root = root.to_string();
+        self
+    }
+
+    /// Puts `separator` before identifier keys in paths instead of `.`.
+    pub fn separator(mut self, separator: char) -> Self {
+        self.options.separator = separator;
+        self
+    }
+
+    /// Lowercases keys in paths, so that `{"Name": 1}` is reported at
+    /// `$.name`. Event keys and the key hook still get the key as written.
+    /// Off by default.
+    pub fn lowercase_keys(mut self, enabled: bool) -> Self {
+        self.options.lowercase_keys = enabled;
+        self
+    }
+
+    /// Also reports paths as keys and indices in
+    /// [`Event::segments`](crate::Event::segments). Off by default.
+    pub fn path_segments(mut self, enabled: bool) -> Self {
+        self.options.path_segments = enabled;
+        self
+    }
+
+    /// Only reports values whose path matches `pattern`, such as
+    /// `$.users[*].email`, where `[*]` matches any array index and `.*` any
+    /// key. Keys are written the way the parser writes them in paths, with
+    /// the configured root and separator.
+    pub fn filter(mut self, pattern: &str) -> Self {
+        self.options.filter = Some(pattern.to_string());
+        self
+    }
+
+    /// Only reports the value at `path`, such as `$.config.database`, and
+    /// the values inside of it. The rest of the document is still read and
+    /// checked for errors, but skipped over like with
+    /// [`JsonParser::skip_value`], without calling the hooks.
+    pub fn subtree(mut self, path: &str) -> Self {
+        self.options.subtree = Some(path.to_string());
+        self
+    }
+
+    /// Calls `on_key` with the path of the object and the decoded key as
+    /// soon as each key has been read, before its value.
+    pub fn on_key(mut self, on_key: impl FnMut(&str, &str) + 'a) -> Self {
+        self.hooks.on_key = Some(Box::new(on_key));
+        self
+    }
+
+    /// Hands the decoded text of string values to `on_chunk` in pieces of
+    /// about `size` bytes, with the path of the value, instead of collecting
+    /// it; the `String` event that follows has an empty value. The last
+    /// piece of each string, which may be empty, is passed with `true`.
+    ///
+    /// Escape sequences, surrogate pairs included, are decoded whole before
+    /// the bytes are counted, so they are never split between chunks, and
+    /// a chunk only ends on a character boundary. Chunks can therefore be
+    /// a few bytes longer or shorter than `size`.
+    pub fn string_chunks(mut self, size: usize, on_chunk: impl FnMut(&str, &str, bool) + 'a) -> Self {
+        self.hooks.on_chunk = Some((size, Box::new(on_chunk)));
+        self
+    }
+
+    /// Passes the text of each number literal to `on_number_text` as it
+    /// was read, before it is checked and reported, and uses the text it
+    /// returns instead, so that `1e+5` can become `1e5`. It is not called
+    /// for `NaN`, `Infinity` and `-Infinity`.
+    pub fn on_number_text(mut self, on_number_text: impl FnMut(&str) -> Cow<'_, str> + 'a) -> Self {
+        self.hooks.on_number_text = Some(Box::new(on_number_text));
+        self
+    }
+
+    /// Calls `on_path` with each value reported at exactly `path`, such as
+    /// `$.config.port`, before it is passed to the value callback. Several
+    /// callbacks for the same path are called in the order they were added.
+    pub fn on_path(mut self, path: &str, on_path: impl FnMut(&Event) + 'a) -> Self {
+        self.hooks.on_path.entry(path.to_string()).or_default().push(Box::new(on_path));
+        self
+    }
+
+    /// Calls `on_complete` once the top-level value has been read and,
+    /// with strict trailing, found to be followed by nothing but
+    /// whitespace, also by [`parse_recovering`](JsonParser::parse_recovering)
+    /// after errors it recovered from. With [`parse_stream`](JsonParser::parse_stream),
+    /// [`parse_next`](JsonParser::parse_next) and
+    /// [`parse_lines`](JsonParser::parse_lines) it is called after each
+    /// value. It is not called when the value callback stopped parsing.
+    pub fn on_complete(mut self, on_complete: impl FnMut() + 'a) -> Self {
+        self.hooks.on_complete = Some(Box::new(on_complete));
+        self
+    }
+
+    /// Creates a parser reading from `reader` and reporting values to
+    /// `on_value`.
+    pub fn build<T, F>(self, reader: T, on_value: F) -> JsonParser<'a, T, F>
+    where
+        T: io::Read,
+        F: FnMut(&Event) -> ControlFlow<()>,
+    {
+        JsonParser::with_options(reader, on_value, self.options, self.hooks)
+    }
+
+    /// Creates a parser reading the document in `data` and reporting values
+    /// to `on_value`, see [`JsonParser::from_slice`].
+    pub fn build_slice<F>(self, data: &'a [u8], on_value: F) -> JsonParser<'a, io::Empty, F>
+    where
+        F: FnMut(&Event) -> ControlFlow<()>,
+    {
+        JsonParser::with_input(io::empty(), Cow::Borrowed(data), on_value, self.options, self.hooks)
+    }
+}
@@ -0,0 +1,332 @@
+//! Deserializing into types implementing `serde::Deserialize`, enabled by
+//! the `serde` feature.
+//!
+//! The [`Deserializer`] pulls events from the parser one at a time, so the
+//! document is never held in memory as a whole.
+
+use std::{error, fmt, io};
+use std::ops::ControlFlow;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+
+use crate::{parse_number, IgnoreEvent, JsonError, JsonParser, JsonParserBuilder, JsonType};
+
+/// Errors from [`from_reader`] and [`from_str`].
+#[derive(Debug)]
+pub enum Error {
+    /// The input is not valid JSON.
+    Parse(JsonError),
+    /// The JSON does not fit the type being deserialized.
+    Message(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Message(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::Message(_) => None,
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl From<JsonError> for Error {
+    fn from(e: JsonError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+/// An event taken from the parser.
+struct Token {
+    typ: JsonType,
+    value: String,
+    key: Option<String>,
+}
+
+/// A `serde::Deserializer` reading one JSON document.
+pub struct Deserializer<T: io::Read> {
+    parser: JsonParser<'static, T, IgnoreEvent>,
+    peeked: Option<Token>,
+}
+
+impl<T: io::Read> Deserializer<T> {
+    pub fn from_reader(reader: T) -> Self {
+        let ignore: IgnoreEvent = |_| ControlFlow::Continue(());
+        Self {
+            parser: JsonParserBuilder::new().container_events(true).build(reader, ignore),
+            peeked: None,
+        }
+    }
+
+    /// Checks that nothing but whitespace follows the value that was read.
+    pub fn end(&mut self) -> Result<(), Error> {
+        Ok(self.parser.finish()?)
+    }
+
+    fn peek(&mut self) -> Result<&mut Token, Error> {
+        if self.peeked.is_none() {
+            let Some(typ) = self.parser.next_event()? else {
+                return Err(Error::Message("no more values in the document".into()));
+            };
+            self.peeked = Some(Token {
+                typ,
+                value: self.parser.current_value().to_string(),
+                key: self.parser.event_member.then(|| self.parser.key.clone()),
+            });
+        }
+        Ok(self.peeked.as_mut().expect("peeked above"))
+    }
+
+    fn next(&mut self) -> Result<Token, Error> {
+        self.peek()?;
+        Ok(self.peeked.take().expect("peeked above"))
+    }
+
+    /// Consumes the end event of a container the visitor is done with.
+    fn close(&mut self, typ: JsonType) -> Result<(), Error> {
+        let found = self.next()?.typ;
+        match typ {
+            _ if found == typ => Ok(()),
+            JsonType::ObjectEnd => Err(Error::Message("object has more members than expected".into())),
+            _ => Err(Error::Message("array has more elements than expected".into())),
+        }
+    }
+}
+
+impl<'de, T: io::Read> de::Deserializer<'de> for &mut Deserializer<T> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let token = self.next()?;
+        match token.typ {
+            JsonType::Null => visitor.visit_unit(),
+            JsonType::True => visitor.visit_bool(true),
+            JsonType::False => visitor.visit_bool(false),
+            JsonType::Integer => {
+                if let Ok(u) = token.value.parse() {
+                    visitor.visit_u64(u)
+                } else if let Ok(i) = token.value.parse() {
+                    visitor.visit_i64(i)
+                } else {
+                    visitor.visit_f64(parse_number(&token.value).map_err(Error::Message)?)
+                }
+            },
+            JsonType::Number => visitor.visit_f64(parse_number(&token.value).map_err(Error::Message)?),
+            JsonType::String => visitor.visit_string(token.value),
+            JsonType::ArrayStart => {
+                let value = visitor.visit_seq(&mut *self)?;
+                self.close(JsonType::ArrayEnd)?;
+                Ok(value)
+            },
+            JsonType::ObjectStart => {
+                let value = visitor.visit_map(&mut *self)?;
+                self.close(JsonType::ObjectEnd)?;
+                Ok(value)
+            },
+            typ => unreachable!("{:?} is not reported with container events", typ),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.peek()?.typ == JsonType::Null {
+            self.next()?;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.peek()?.typ {
+            JsonType::String => visitor.visit_enum(self.next()?.value.into_deserializer()),
+            JsonType::ObjectStart => {
+                self.next()?;
+                if self.peek()?.typ == JsonType::ObjectEnd {
+                    return Err(Error::Message("expected an object with a single variant".into()));
+                }
+                let value = visitor.visit_enum(&mut *self)?;
+                self.close(JsonType::ObjectEnd)?;
+                Ok(value)
+            },
+            _ => Err(Error::Message("expected a string or an object for an enum".into())),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de, T: io::Read> de::SeqAccess<'de> for Deserializer<T> {
+    type Error = Error;
+
+    fn next_element_seed<S: de::DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>, Error> {
+        if self.peek()?.typ == JsonType::ArrayEnd {
+            return Ok(None);
+        }
+        seed.deserialize(self).map(Some)
+    }
+}
+
+impl<'de, T: io::Read> de::MapAccess<'de> for Deserializer<T> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        let token = self.peek()?;
+        if token.typ == JsonType::ObjectEnd {
+            return Ok(None);
+        }
+        let key = token.key.take().expect("members are reported with their key");
+        seed.deserialize(key.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<S: de::DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, Error> {
+        seed.deserialize(self)
+    }
+}
+
+impl<'de, T: io::Read> de::EnumAccess<'de> for &mut Deserializer<T> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Self), Error> {
+        let key = self.peek()?.key.take().expect("members are reported with their key");
+        let variant = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(key))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, T: io::Read> de::VariantAccess<'de> for &mut Deserializer<T> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+/// Deserializes a `T` from a reader holding one JSON document.
+pub fn from_reader<R: io::Read, T: DeserializeOwned>(reader: R) -> Result<T, Error> {
+    let mut de = Deserializer::from_reader(reader);
+    let value = T::deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+/// Deserializes a `T` from a string holding one JSON document.
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T, Error> {
+    from_reader(s.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct User {
+        name: String,
+        age: u32,
+        email: Option<String>,
+        tags: Vec<String>,
+        score: f64,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Point(i32, i32),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[test]
+    fn test_struct() {
+        let data = r#"{"name": "a", "age": 30, "email": null, "extra": {"x": [1, 2]}, "tags": ["x", "y"], "score": -1.5e1}"#;
+        let user: User = from_str(data).unwrap();
+        assert_eq!(User {
+            name: "a".into(),
+            age: 30,
+            email: None,
+            tags: vec!["x".into(), "y".into()],
+            score: -15.0,
+        }, user);
+
+        let users: Vec<User> = from_reader(br#"[{"name": "b", "age": 1, "email": "b@x", "tags": [], "score": 0}]"#.as_slice()).unwrap();
+        assert_eq!(Some("b@x".to_string()), users[0].email);
+    }
+
+    #[test]
+    fn test_collections_and_scalars() {
+        let m: HashMap<String, Vec<i64>> = from_str(r#"{"a": [1, -2], "b": []}"#).unwrap();
+        assert_eq!(vec![1, -2], m["a"]);
+        assert!(m["b"].is_empty());
+
+        assert_eq!((1u8, true, 'x'), from_str::<(u8, bool, char)>(r#"[1, true, "x"]"#).unwrap());
+        assert_eq!(Some(5), from_str::<Option<u16>>("5").unwrap());
+        assert_eq!(18446744073709551615u64, from_str::<u64>("18446744073709551615").unwrap());
+    }
+
+    #[test]
+    fn test_enums() {
+        let shapes: Vec<Shape> = from_str(r#"["Empty", {"Circle": 2.5}, {"Point": [1, -1]}, {"Rect": {"w": 2, "h": 3}}]"#).unwrap();
+        assert_eq!(vec![Shape::Empty, Shape::Circle(2.5), Shape::Point(1, -1), Shape::Rect { w: 2, h: 3 }], shapes);
+
+        assert!(from_str::<Shape>(r#"{}"#).is_err());
+        assert!(from_str::<Shape>(r#""Square""#).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = from_str::<User>(r#"{"name": "a""#).unwrap_err();
+        assert!(matches!(err, Error::Parse(JsonError::UnexpectedEof { .. })), "{:?}", err);
+
+        let err = from_str::<User>(r#"{"name": 1}"#).unwrap_err();
+        assert!(matches!(err, Error::Message(_)), "{:?}", err);
+
+        assert!(from_str::<u8>("256").is_err());
+        assert!(from_str::<(u8, u8)>("[1, 2, 3]").is_err());
+        let err = from_str::<(u8, u8)>("[1, 2, {}]").unwrap_err();
+        assert_eq!("array has more elements than expected", err.to_string());
+        assert!(matches!(from_str::<u8>("1 2"), Err(Error::Parse(JsonError::TrailingData { .. }))));
+    }
+}
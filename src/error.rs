@@ -0,0 +1,113 @@
+use std::{error, fmt, io};
+
+/// The location in the input where an error was detected.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Position {
+    /// Number of bytes consumed from the input, including the offending byte.
+    pub offset: usize,
+    /// Counted from 1. Lines end at `\n`, so `\r\n` ends a single line and
+    /// a lone `\r` does not end one.
+    pub line: usize,
+    pub column: usize,
+    /// The path of the value at or right before the error.
+    pub path: String,
+}
+
+/// The result of parsing, with a [`JsonError`] on failure.
+pub type JsonResult<T> = Result<T, JsonError>;
+
+#[derive(Debug)]
+pub enum JsonError {
+    UnexpectedChar { msg: String, pos: Position },
+    UnterminatedString { pos: Position },
+    InvalidNumber { msg: String, pos: Position },
+    InvalidEscape { msg: String, pos: Position },
+    InvalidUtf8 { pos: Position },
+    UnexpectedEof { msg: String, pos: Position },
+    TrailingData { pos: Position },
+    DepthExceeded { max: usize, pos: Position },
+    DuplicateKey { key: String, pos: Position },
+    StringTooLong { max: usize, pos: Position },
+    ValueTooLong { max: usize, pos: Position },
+    InputTooLarge { max: usize, pos: Position },
+    TooManyElements { max: usize, pos: Position },
+    TooManyMembers { max: usize, pos: Position },
+    /// The cancel flag was set, see
+    /// [`JsonParserBuilder::cancel_flag`](crate::JsonParserBuilder::cancel_flag).
+    Cancelled { pos: Position },
+    /// An error inside the given record (counting from 1) of a JSON lines input.
+    Record { record: usize, error: Box<JsonError> },
+    Io { error: io::Error, pos: Position },
+}
+
+impl JsonError {
+    pub fn position(&self) -> &Position {
+        match self {
+            JsonError::UnexpectedChar { pos, .. }
+            | JsonError::UnterminatedString { pos }
+            | JsonError::InvalidNumber { pos, .. }
+            | JsonError::InvalidEscape { pos, .. }
+            | JsonError::InvalidUtf8 { pos }
+            | JsonError::UnexpectedEof { pos, .. }
+            | JsonError::TrailingData { pos }
+            | JsonError::DepthExceeded { pos, .. }
+            | JsonError::DuplicateKey { pos, .. }
+            | JsonError::StringTooLong { pos, .. }
+            | JsonError::ValueTooLong { pos, .. }
+            | JsonError::InputTooLarge { pos, .. }
+            | JsonError::TooManyElements { pos, .. }
+            | JsonError::TooManyMembers { pos, .. }
+            | JsonError::Cancelled { pos }
+            | JsonError::Io { pos, .. } => pos,
+            JsonError::Record { error, .. } => error.position(),
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.position().offset
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::UnexpectedChar { msg, .. }
+            | JsonError::InvalidNumber { msg, .. }
+            | JsonError::InvalidEscape { msg, .. }
+            | JsonError::UnexpectedEof { msg, .. } => f.write_str(msg)?,
+            JsonError::UnterminatedString { .. } => f.write_str("unterminated string")?,
+            JsonError::InvalidUtf8 { .. } => f.write_str("invalid UTF-8 in string")?,
+            JsonError::TrailingData { .. } => f.write_str("unexpected data after the top-level value")?,
+            JsonError::DepthExceeded { max, .. } => write!(f, "maximum nesting depth exceeded ({})", max)?,
+            JsonError::DuplicateKey { key, .. } => write!(f, "duplicate key '{}'", key)?,
+            JsonError::StringTooLong { max, .. } => write!(f, "string longer than {} bytes", max)?,
+            JsonError::ValueTooLong { max, .. } => write!(f, "value longer than {} bytes", max)?,
+            JsonError::InputTooLarge { max, .. } => write!(f, "input size limit exceeded ({} bytes)", max)?,
+            JsonError::TooManyElements { max, .. } => write!(f, "array has more than {} elements", max)?,
+            JsonError::TooManyMembers { max, .. } => write!(f, "object has more than {} members", max)?,
+            JsonError::Cancelled { .. } => f.write_str("parsing cancelled")?,
+            JsonError::Io { error, .. } => write!(f, "{}", error)?,
+            JsonError::Record { record, error } => return write!(f, "record {}: {}", record, error),
+        }
+
+        let pos = self.position();
+        write!(f, " at {}, line {} column {}", pos.path, pos.line, pos.column)
+    }
+}
+
+/// Wraps an error from outside of the parser, which has no position.
+impl From<io::Error> for JsonError {
+    fn from(error: io::Error) -> Self {
+        JsonError::Io { error, pos: Position::default() }
+    }
+}
+
+impl error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            JsonError::Io { error, .. } => Some(error),
+            JsonError::Record { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,383 @@
+//! Incremental parsing from bytes pushed into the parser as they arrive.
+//!
+//! The parser itself only knows how to read synchronously, so it reads
+//! from a [`Feed`] holding the bytes received so far. When the feed runs
+//! dry in the middle of an event, the parser keeps what it has read of the
+//! token, such as the decoded part of a string, and carries on with it once
+//! more bytes have arrived. Only escapes, literals and other short tokens
+//! are read again from their start.
+
+use std::io;
+use std::ops::ControlFlow;
+
+use crate::{would_block, Event, JsonError, JsonParser, JsonType, READ_BUFFER_SIZE};
+
+/// The input of an incremental parser: the bytes given to
+/// [`JsonParser::feed`] that the parser may still need.
+///
+/// Create the parser with `JsonParser::new(Feed::default(), on_value)`, or
+/// pass `Feed::default()` to [`JsonParserBuilder::build`](crate::JsonParserBuilder::build).
+#[derive(Default)]
+pub struct Feed {
+    data: Vec<u8>,
+    /// The input offset of `data[0]`.
+    base: usize,
+    pos: usize,
+    eof: bool,
+    /// Set once nothing more will be parsed.
+    done: bool,
+}
+
+impl io::Read for Feed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.data.len() {
+            return if self.eof { Ok(0) } else { Err(io::ErrorKind::WouldBlock.into()) };
+        }
+        let n = buf.len().min(self.data.len() - self.pos);
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Feed {
+    fn push(&mut self, data: &[u8]) {
+        self.data.extend_from_slice(data);
+    }
+
+    fn close(&mut self) {
+        self.eof = true;
+    }
+
+    /// Forgets the bytes before input offset `offset`, which the parser is
+    /// done with.
+    fn discard(&mut self, offset: usize) {
+        let n = offset - self.base;
+        if n >= READ_BUFFER_SIZE {
+            self.data.drain(..n);
+            self.base = offset;
+            self.pos -= n;
+        }
+    }
+}
+
+/// The outcome of [`JsonParser::feed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// More input is needed.
+    Pending,
+    /// The top-level value has been parsed.
+    Done,
+}
+
+impl<F> JsonParser<'_, Feed, F> where
+    F: FnMut(&Event) -> ControlFlow<()>
+{
+    /// Parses as much of `bytes`, and the bytes fed before, as possible.
+    ///
+    /// Returns [`Status::Pending`] when the input ends in the middle of the
+    /// document, and [`Status::Done`] once the top-level value is complete
+    /// or the callback returned `ControlFlow::Break`. A number at the top
+    /// level only completes once the input is closed, since more digits
+    /// could follow.
+    ///
+    /// The hooks are called as the input is parsed, the number text hook
+    /// once the whole number has been read.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Status, JsonError> {
+        if !self.reader.done {
+            self.reader.push(bytes);
+        }
+        self.advance()
+    }
+
+    /// Marks the end of the input and parses what is left, failing if the
+    /// document is incomplete.
+    pub fn close(&mut self) -> Result<(), JsonError> {
+        self.reader.close();
+        match self.advance()? {
+            Status::Done => Ok(()),
+            Status::Pending => unreachable!("a closed feed never runs dry"),
+        }
+    }
+
+    fn advance(&mut self) -> Result<Status, JsonError> {
+        while !self.reader.done {
+            match self.try_next_event()? {
+                Some(Some(typ)) => {
+                    if self.report(typ).is_break() {
+                        self.reader.done = true;
+                    }
+                },
+                Some(None) => {
+                    if !self.options.strict_trailing || self.try_finish()? {
+                        self.reader.done = true;
+                        self.complete();
+                    } else {
+                        return Ok(Status::Done);
+                    }
+                },
+                None => return Ok(Status::Pending),
+            }
+        }
+        Ok(Status::Done)
+    }
+
+    /// Reads the next event like `next_event`, or returns `Ok(None)` when
+    /// more input is needed first.
+    fn try_next_event(&mut self) -> Result<Option<Option<JsonType>>, JsonError> {
+        match self.next_event() {
+            Ok(event) => {
+                self.reader.discard(self.offset);
+                Ok(Some(event))
+            },
+            Err(e) if would_block(&e) => {
+                self.suspend();
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Gets ready to carry on once more input has arrived, going back to
+    /// the start of the short token the input ran dry in, if any.
+    fn suspend(&mut self) {
+        if let Some(mark) = self.rewind.take() {
+            self.offset = mark.offset;
+            self.line = mark.line;
+            self.column = mark.column;
+            self.prev_column = mark.prev_column;
+            self.input_pos = 0;
+            self.input_len = 0;
+            self.ungets.clear();
+            self.bytes_read = mark.offset;
+            self.reader.pos = mark.offset - self.reader.base;
+        }
+        self.reader.discard(self.offset);
+    }
+
+    /// Checks for trailing data like `finish`, or returns `Ok(false)` when
+    /// more input is needed first.
+    fn try_finish(&mut self) -> Result<bool, JsonError> {
+        match self.finish() {
+            Ok(()) => Ok(true),
+            Err(e) if would_block(&e) => {
+                self.suspend();
+                Ok(false)
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::{json_parse, JsonParserBuilder};
+
+    fn feed_events(chunks: &[&[u8]]) -> (Vec<Status>, Result<Vec<String>, JsonError>) {
+        let mut out = Vec::new();
+        let mut statuses = Vec::new();
+        let mut parser = JsonParser::new(Feed::default(), |e: &Event| {
+            out.push(format!("{} {:?} {}", e.path, e.typ, e.value));
+            ControlFlow::Continue(())
+        });
+        for chunk in chunks {
+            match parser.feed(chunk) {
+                Ok(status) => statuses.push(status),
+                Err(e) => return (statuses, Err(e)),
+            }
+        }
+        let result = parser.close();
+        (statuses, result.map(|_| out))
+    }
+
+    #[test]
+    fn test_feed() {
+        let data = br#" {"key": "a longer \u00e9 string", "n": [1.5, -20, true, null], "o": {"": {}}}"#;
+        let mut expected = Vec::new();
+        json_parse(data.as_slice(), |p, t, v| expected.push(format!("{} {:?} {}", p, t, v))).unwrap();
+
+        let chunks: Vec<&[u8]> = data.chunks(1).collect();
+        let (statuses, out) = feed_events(&chunks);
+        assert_eq!(expected, out.unwrap());
+        assert!(statuses[..statuses.len() - 1].iter().all(|s| *s == Status::Pending));
+        assert_eq!(Some(&Status::Done), statuses.last());
+
+        let (statuses, out) = feed_events(&[b"[1, 2", b"3]  ", b" \n"]);
+        assert_eq!(vec![Status::Pending, Status::Done, Status::Done], statuses);
+        assert_eq!(vec!["$[0] Integer 1", "$[1] Integer 23", "$ Array "], out.unwrap());
+
+        let (statuses, out) = feed_events(&[b"12", b"3"]);
+        assert_eq!(vec![Status::Pending, Status::Pending], statuses);
+        assert_eq!(vec!["$ Integer 123"], out.unwrap());
+    }
+
+    #[test]
+    fn test_feed_errors() {
+        let (_, out) = feed_events(&[b"[1, ", b"x]"]);
+        let err = out.unwrap_err();
+        assert!(matches!(err, JsonError::UnexpectedChar { .. }), "{:?}", err);
+        assert_eq!(5, err.offset());
+
+        assert!(matches!(feed_events(&[b"[1", b", 2"]).1, Err(JsonError::UnexpectedEof { .. })));
+        assert!(matches!(feed_events(&[b"[1]", b" 2"]).1, Err(JsonError::TrailingData { .. })));
+        assert!(matches!(feed_events(&[]).1, Err(JsonError::UnexpectedEof { .. })));
+    }
+
+    #[test]
+    fn test_feed_hooks() {
+        let data = br#"{"key": "abcdef", "n": [12, "xyz"], "k2": {"k3": 3.5}}"#;
+        for size in [1, 3, data.len()] {
+            let keys = RefCell::new(Vec::new());
+            let chunks = RefCell::new(String::new());
+            let mut numbers = Vec::new();
+            let mut parser = JsonParserBuilder::new()
+                .on_key(|path, key| keys.borrow_mut().push(format!("{}/{}", path, key)))
+                .string_chunks(2, |_, chunk, last| {
+                    chunks.borrow_mut().push_str(chunk);
+                    if last {
+                        chunks.borrow_mut().push('|');
+                    }
+                })
+                .on_number_text(|text| {
+                    numbers.push(text.to_string());
+                    Cow::Borrowed(text)
+                })
+                .build(Feed::default(), |_: &Event| ControlFlow::Continue(()));
+            for chunk in data.chunks(size) {
+                parser.feed(chunk).unwrap();
+            }
+            parser.close().unwrap();
+            drop(parser);
+
+            assert_eq!(vec!["$/key", "$/n", "$/k2", "$.k2/k3"], keys.into_inner(), "{}", size);
+            assert_eq!("abcdef|xyz|", chunks.into_inner(), "{}", size);
+            assert_eq!(vec!["12", "3.5"], numbers, "{}", size);
+        }
+    }
+
+    #[test]
+    fn test_feed_number_text() {
+        let mut numbers = Vec::new();
+        let mut out = Vec::new();
+        let mut parser = JsonParserBuilder::new()
+            .allow_nan(true)
+            .on_number_text(|text| {
+                numbers.push(text.to_string());
+                Cow::Borrowed(text)
+            })
+            .build(Feed::default(), |e: &Event| {
+                out.push(e.value.to_string());
+                ControlFlow::Continue(())
+            });
+        for chunk in [b"[-I".as_slice(), b"nfinity, -", b"1", b"2]"] {
+            parser.feed(chunk).unwrap();
+        }
+        parser.close().unwrap();
+        drop(parser);
+        assert_eq!(vec!["-12"], numbers);
+        assert_eq!(vec!["-Infinity", "-12", ""], out);
+    }
+
+    #[test]
+    fn test_feed_long_string() {
+        let mut data = b"[\"".to_vec();
+        data.extend(r"abcé\n😀 ".repeat(200_000).bytes());
+        data.extend(b"\"]");
+        let mut out = Vec::new();
+        let mut parser = JsonParser::new(Feed::default(), |e: &Event| {
+            out.push(e.value.to_string());
+            ControlFlow::Continue(())
+        });
+        for chunk in data.chunks(256) {
+            parser.feed(chunk).unwrap();
+            assert!(parser.reader.data.len() < 2 * READ_BUFFER_SIZE, "{}", parser.reader.data.len());
+        }
+        parser.close().unwrap();
+        drop(parser);
+        assert_eq!(vec!["abc\u{e9}\n\u{1f600} ".repeat(200_000), String::new()], out);
+    }
+
+    /// Parses `data` from a slice, and fed in three parts split at every
+    /// pair of positions, checking that the events, hook calls and errors
+    /// are the same, with and without the key and chunk hooks.
+    fn check_splits(data: &[u8], options: fn(JsonParserBuilder<'_>) -> JsonParserBuilder<'_>) {
+        let run = |parts: &[&[u8]], hooks: bool| {
+            let out = RefCell::new(Vec::new());
+            let mut builder = options(JsonParserBuilder::new());
+            if hooks {
+                builder = builder
+                    .on_key(|path, key| out.borrow_mut().push(format!("key {} {}", path, key)))
+                    .string_chunks(3, |path, chunk, last| out.borrow_mut().push(format!("chunk {} {} {}", path, chunk, last)));
+            }
+            let result = if let [data] = parts {
+                builder
+                    .build_slice(data, |e: &Event| {
+                        out.borrow_mut().push(format!("{} {:?} {} {}", e.path, e.typ, e.value, e.offset));
+                        ControlFlow::Continue(())
+                    })
+                    .parse()
+            } else {
+                let mut parser = builder.build(Feed::default(), |e: &Event| {
+                    out.borrow_mut().push(format!("{} {:?} {} {}", e.path, e.typ, e.value, e.offset));
+                    ControlFlow::Continue(())
+                });
+                parts.iter().try_for_each(|part| parser.feed(part).map(drop)).and_then(|_| parser.close())
+            };
+            (out.into_inner(), result.map_err(|e| e.to_string()))
+        };
+
+        for hooks in [false, true] {
+            let expected = run(&[data], hooks);
+            for i in 0..=data.len() {
+                for j in i..=data.len() {
+                    assert_eq!(expected, run(&[&data[..i], &data[i..j], &data[j..]], hooks), "split at {} and {}", i, j);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_feed_splits() {
+        let data = "\u{feff}/* a /* b */ c */ {\"k\\u00e9y\": \"a\\\\b\\\"c\\ud83d\\ude00\u{e9}\", // x\n\
+            \"n\": [-Infinity, NaN, -1.5e3, true, false, null, 12]}\n x";
+        check_splits(data.as_bytes(), |b| b.allow_comments(true).allow_nan(true));
+        check_splits(r#"["aé\"b", "\\", 'c\'d']"#.as_bytes(), |b| b.raw_strings(true).single_quotes(true));
+        check_splits(r#"{"skip": {"a": ["xé", 1.5, true]}, "keep": [1, "y"], "b": ["z"]}"#.as_bytes(), |b| b.subtree("$.keep"));
+    }
+
+    #[test]
+    fn test_feed_duplicate_keys() {
+        let feed = |data: &[u8]| {
+            let mut parser = JsonParserBuilder::new()
+                .reject_duplicate_keys(true)
+                .build(Feed::default(), |_: &Event| ControlFlow::Continue(()));
+            for chunk in data.chunks(1) {
+                parser.feed(chunk)?;
+            }
+            parser.close()
+        };
+        assert!(feed(br#"{"a": {"x": 1, "y": [2]}, "b": 2, "c": {}}"#).is_ok());
+        let err = feed(br#"{"a": 1, "b": {"a": 2}, "a": 3}"#).unwrap_err();
+        assert!(matches!(err, JsonError::DuplicateKey { ref key, .. } if key == "a"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_feed_stops() {
+        let mut count = 0;
+        let mut parser = JsonParserBuilder::new()
+            .strict_trailing(false)
+            .build(Feed::default(), |_: &Event| {
+                count += 1;
+                ControlFlow::Break(())
+            });
+        assert_eq!(Status::Pending, parser.feed(b"[\"a").unwrap());
+        assert_eq!(Status::Done, parser.feed(b"\", \"b\"]").unwrap());
+        assert_eq!(Status::Done, parser.feed(b" garbage").unwrap());
+        parser.close().unwrap();
+        assert_eq!(1, count);
+    }
+}
@@ -1,249 +1,4151 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::io;
+use std::ops::ControlFlow;
+use std::sync::atomic::Ordering;
 
-#[derive(Debug, PartialEq, Eq)]
+use builder::{Hooks, Options};
+
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+const DEFAULT_MAX_DEPTH: usize = 128;
+/// Room reserved in the path for each level of `expected_depth`.
+const PATH_BYTES_PER_LEVEL: usize = 16;
+/// How many bytes are read between looks at the cancel flag.
+const CANCEL_CHECK_INTERVAL: usize = 4 * 1024;
+
+#[cfg(feature = "async")]
+mod async_io;
+mod builder;
+#[cfg(feature = "serde")]
+pub mod de;
+mod error;
+mod incremental;
+mod path;
+mod value;
+mod writer;
+
+#[cfg(feature = "async")]
+pub use async_io::json_parse_async;
+pub use builder::JsonParserBuilder;
+#[cfg(feature = "serde")]
+pub use de::{from_reader, from_str};
+pub use error::{JsonError, JsonResult, Position};
+pub use incremental::{Feed, Status};
+pub use value::{parse_to_value, to_compact_string, to_pretty_string, Number, Value};
+pub use writer::JsonWriter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum JsonType {
-    None,
     String,
+    /// A number with a fraction or an exponent, such as `1.5` or `1e3`.
     Number,
+    /// A number made of only an integer part, such as `42` or `-0`.
+    Integer,
     True,
     False,
     Null,
+    /// Reported after all elements of an array.
     Array,
+    /// Reported after all members of an object.
     Object,
+    /// Reported when entering an array, replacing `Array` when container
+    /// events are enabled.
+    ArrayStart,
+    /// Reported when leaving an array when container events are enabled.
+    ArrayEnd,
+    /// Reported when entering an object, replacing `Object` when container
+    /// events are enabled.
+    ObjectStart,
+    /// Reported when leaving an object when container events are enabled.
+    ObjectEnd,
+}
+
+impl JsonType {
+    /// Strings, numbers, booleans and null.
+    pub fn is_scalar(self) -> bool {
+        !self.is_container()
+    }
+
+    /// Arrays and objects, including their start and end events.
+    pub fn is_container(self) -> bool {
+        matches!(self,
+            JsonType::Array | JsonType::ArrayStart | JsonType::ArrayEnd
+            | JsonType::Object | JsonType::ObjectStart | JsonType::ObjectEnd)
+    }
+
+    /// `True` and `False`.
+    pub fn is_bool(self) -> bool {
+        matches!(self, JsonType::True | JsonType::False)
+    }
+}
+
+/// Shows the JSON name of the type: `number` for both kinds of numbers,
+/// and `array` or `object` for all container events.
+impl std::fmt::Display for JsonType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            JsonType::String => "string",
+            JsonType::Number | JsonType::Integer => "number",
+            JsonType::True => "true",
+            JsonType::False => "false",
+            JsonType::Null => "null",
+            JsonType::Array | JsonType::ArrayStart | JsonType::ArrayEnd => "array",
+            JsonType::Object | JsonType::ObjectStart | JsonType::ObjectEnd => "object",
+        })
+    }
+}
+
+/// What a value is directly inside of, see [`Event::parent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parent {
+    /// The top-level value.
+    Root,
+    ArrayElement,
+    ObjectMember,
+}
+
+/// A step in the path of a value, see [`Event::segments`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment<'a> {
+    /// The decoded key of an object member.
+    Key(&'a str),
+    /// The index of an array element.
+    Index(usize),
+}
+
+/// A value reported by [`JsonParser`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Event<'a> {
+    pub path: &'a str,
+    pub typ: JsonType,
+    pub value: &'a str,
+    /// Byte offset of the first byte of the value in the input. End events
+    /// carry the offset of the opening bracket.
+    pub offset: usize,
+    /// The decoded key of the member when the value is directly inside an
+    /// object.
+    pub key: Option<&'a str>,
+    /// The index of the element when the value is directly inside an
+    /// array.
+    pub index: Option<usize>,
+    /// Whether the value is the top-level value, an element or a member.
+    pub parent: Parent,
+    /// The path as keys and indices after the root, such as `a`, `2`, `b`
+    /// for `$.a[2].b`, when
+    /// [`path_segments`](JsonParserBuilder::path_segments) is on, and empty
+    /// otherwise. Keys are not lowercased.
+    pub segments: &'a [PathSegment<'a>],
+    /// Number of arrays and objects around the value, 0 for the top-level
+    /// value.
+    pub depth: usize,
+    /// Set on the end event of an array or object that has no elements or
+    /// members.
+    pub empty: bool,
+    /// The number of elements or members, on the end event of an array or
+    /// object. 0 for other values.
+    pub len: usize,
+}
+
+/// Counts of the values read so far, see [`JsonParser::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct ParseStats {
+    pub objects: usize,
+    pub arrays: usize,
+    pub strings: usize,
+    /// Integers and other numbers.
+    pub numbers: usize,
+    pub booleans: usize,
+    pub nulls: usize,
+    /// The deepest nesting of arrays and objects, 1 for `[]`.
+    pub max_depth: usize,
+}
+
+type KeyHook<'a> = Box<dyn FnMut(&str, &str) + 'a>;
+type ChunkHook<'a> = Box<dyn FnMut(&str, &str, bool) + 'a>;
+type PathHook<'a> = Box<dyn FnMut(&Event) + 'a>;
+type CompleteHook<'a> = Box<dyn FnMut() + 'a>;
+type NumberHook<'a> = Box<dyn FnMut(&str) -> Cow<'_, str> + 'a>;
+
+/// Where a container is between its tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Right after the opening bracket.
+    First,
+    /// After a `,`, expecting an element or member.
+    AfterComma,
+    /// After an object key, expecting the `:`.
+    Colon,
+    /// After the `:` of a member, expecting its value.
+    MemberValue,
+    /// After an element or member value, expecting `,` or the closing bracket.
+    AfterValue,
+}
+
+/// An array or object that has been opened but not yet closed.
+#[derive(Clone)]
+struct Frame {
+    object: bool,
+    state: State,
+    /// Index of the current element or member.
+    index: usize,
+    /// Length of the path of the container itself.
+    path_len: usize,
+    /// Offset of the opening bracket.
+    start: usize,
+    /// The member key of the container, reported again with its end event.
+    key: Option<String>,
+    /// The element index of the container, reported again with its end
+    /// event.
+    element: Option<usize>,
+    /// Keys read so far, when duplicate keys are rejected.
+    seen: HashSet<String>,
+}
+
+/// A token that an incremental parser got partway through before the input
+/// ran dry, to carry on with once more input has arrived.
+#[derive(Debug, Clone, Copy)]
+enum Partial {
+    /// A value starting with `c`, to begin again.
+    Value { c: u8, member: bool },
+    /// A string, with the bytes decoded so far in `buf`.
+    String { key: bool, quote: u8 },
+    /// A number, with the text read so far in `value`.
+    Number,
+    /// A comment nested `depth` deep, or a line comment for 0.
+    Comment { depth: usize, prev: u8 },
+}
+
+/// A read position, to go back to.
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    offset: usize,
+    line: usize,
+    column: usize,
+    prev_column: usize,
 }
 
-pub struct JsonParser<T, F> where
+pub struct JsonParser<'a, T, F> where
     T: std::io::Read,
-    F: FnMut(&str, JsonType, &str) 
+    F: FnMut(&Event) -> ControlFlow<()>
 {
     reader: T,
+    /// The buffer the reader reads into, or the whole input when parsing a
+    /// slice.
+    input: Cow<'a, [u8]>,
+    input_pos: usize,
+    input_len: usize,
     ungets: Vec<u8>,
+    /// Bytes taken from `input`, not counting those served again from
+    /// `ungets`.
+    bytes_read: usize,
+    /// The value of `bytes_read` at which to look at the cancel flag next.
+    next_cancel_check: usize,
     path :String,
     value: String,
+    /// A string value borrowed from the input slice instead of copied to
+    /// `value`.
+    borrowed: Option<&'a str>,
+    key: String,
+    buf: Vec<u8>,
+    offset: usize,
+    line: usize,
+    column: usize,
+    prev_column: usize,
+    stack: Vec<Frame>,
+    started: bool,
+    /// The byte most recently returned by `getc`.
+    last: u8,
+    /// Set when reading a string failed before its closing quote.
+    in_string: bool,
+    /// The offset right after the most recently read number.
+    number_end: Option<usize>,
+    /// Set while `skip_value` reads a value, or one outside the subtree is
+    /// skipped, which builds no paths and calls no hooks.
+    skipping: bool,
+    /// The depth at which a value outside the subtree is being skipped.
+    subtree_skip: Option<usize>,
+    /// The token the input ran dry in.
+    partial: Option<Partial>,
+    /// Where to read again from after the input ran dry, for the few
+    /// short tokens, such as escapes and literals, that are read whole.
+    rewind: Option<Mark>,
+    /// The length of the string being read, counting the chunks already
+    /// passed to the chunk hook.
+    string_len: usize,
+    event_offset: usize,
+    event_member: bool,
+    event_index: Option<usize>,
+    event_depth: usize,
+    event_empty: bool,
+    event_len: usize,
+    options: Options,
     on_value: F,
+    hooks: Hooks<'a>,
+    stats: ParseStats,
+    /// Kept between events for its allocation, see `recycle`.
+    segments: Vec<PathSegment<'static>>,
 }
 
-impl<T, F> JsonParser<T, F> where
+impl<'a, F> JsonParser<'a, io::Empty, F> where
+    F: FnMut(&Event) -> ControlFlow<()>
+{
+    /// Creates a parser reading the document in `data`, with the default
+    /// options.
+    ///
+    /// The bytes are parsed where they are instead of being copied to a
+    /// buffer first, and string values without escapes are reported as
+    /// slices of `data`. Use [`JsonParserBuilder::build_slice`] for other
+    /// options.
+    pub fn from_slice(data: &'a [u8], on_value: F) -> Self {
+        Self::with_input(io::empty(), Cow::Borrowed(data), on_value, Options::default(), Hooks::default())
+    }
+}
+
+impl<'a, T, F> JsonParser<'a, T, F> where
     T: std::io::Read, 
-    F: FnMut(&str, JsonType, &str) 
+    F: FnMut(&Event) -> ControlFlow<()>
 {
+    /// Creates a parser reporting values to `on_value`, with the default
+    /// options. Parsing stops early once the callback returns
+    /// `ControlFlow::Break`. Use [`JsonParserBuilder`] for other options.
+    ///
+    /// Input split over several sources can be read through
+    /// [`io::Read::chain`]; values may span the boundary.
     pub fn new(reader : T, on_value: F) -> Self {
+        Self::with_options(reader, on_value, Options::default(), Hooks::default())
+    }
+
+    fn with_options(reader: T, on_value: F, options: Options, hooks: Hooks<'a>) -> Self {
+        Self::with_input(reader, Cow::Owned(vec![0; READ_BUFFER_SIZE]), on_value, options, hooks)
+    }
+
+    fn with_input(reader: T, input: Cow<'a, [u8]>, on_value: F, options: Options, hooks: Hooks<'a>) -> Self {
+        let input_len = match input {
+            Cow::Borrowed(data) => data.len(),
+            Cow::Owned(_) => 0,
+        };
+        let mut path = String::with_capacity(options.root.len() + options.expected_depth * PATH_BYTES_PER_LEVEL);
+        path.push_str(&options.root);
         Self {
             reader,
+            input,
+            input_pos: 0,
+            input_len,
             ungets: Vec::new(),
-            path: String::from("$"),
-            value: String::new(),
+            bytes_read: 0,
+            next_cancel_check: if options.cancel.is_some() { CANCEL_CHECK_INTERVAL } else { usize::MAX },
+            path,
+            value: String::with_capacity(options.value_capacity),
+            borrowed: None,
+            key: String::new(),
+            buf: Vec::with_capacity(options.value_capacity),
+            offset: 0,
+            line: 1,
+            column: 0,
+            prev_column: 0,
+            stack: Vec::with_capacity(options.expected_depth),
+            started: false,
+            last: 0,
+            in_string: false,
+            number_end: None,
+            skipping: false,
+            subtree_skip: None,
+            partial: None,
+            rewind: None,
+            string_len: 0,
+            event_offset: 0,
+            event_member: false,
+            event_index: None,
+            event_depth: 0,
+            event_empty: false,
+            event_len: 0,
+            options,
             on_value,
+            hooks,
+            stats: ParseStats::default(),
+            segments: Vec::new(),
         }
     }
 
-    fn getc(&mut self) -> Result<Option<u8>, String> {
-        if let Some(u) = self.ungets.pop() {
-            return Ok(Some(u));
+    /// Prepares the parser for a new document read from `reader`, keeping
+    /// the options, the callback and the allocated buffers.
+    pub fn reset(&mut self, reader: T) {
+        self.reader = reader;
+        if let Cow::Borrowed(_) = self.input {
+            self.input = Cow::Owned(vec![0; READ_BUFFER_SIZE]);
         }
-
-        let mut b: [u8; 1] = [0];
-        match self.reader.read_exact(&mut b) {
-            Ok(()) => Ok(Some(b[0])),
-            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
-            Err(e) => Err(e.to_string()),
+        self.input_pos = 0;
+        self.input_len = 0;
+        self.ungets.clear();
+        self.bytes_read = 0;
+        if self.options.cancel.is_some() {
+            self.next_cancel_check = CANCEL_CHECK_INTERVAL;
         }
+        self.path.clear();
+        self.path.push_str(&self.options.root);
+        self.value.clear();
+        self.borrowed = None;
+        self.key.clear();
+        self.offset = 0;
+        self.line = 1;
+        self.column = 0;
+        self.prev_column = 0;
+        self.stack.clear();
+        self.started = false;
+        self.last = 0;
+        self.in_string = false;
+        self.number_end = None;
+        self.skipping = false;
+        self.subtree_skip = None;
+        self.partial = None;
+        self.rewind = None;
+        self.stats = ParseStats::default();
     }
 
-    fn ungetc(&mut self, c: u8) {
-        self.ungets.push(c);
+    /// The path of the value most recently reported, or of the container
+    /// being read, once reading has stopped in between. Mostly useful after
+    /// the callback returned `ControlFlow::Break`.
+    pub fn current_path(&self) -> &str {
+        &self.path
     }
 
-    fn emit(&mut self, typ: JsonType) {
-        (self.on_value)(&self.path, typ, &self.value);
+    /// The value most recently reported, empty for containers.
+    pub fn current_value(&self) -> &str {
+        self.borrowed.unwrap_or(&self.value)
     }
 
-    fn read_value(&mut self) -> Result<(), String> {
-        while let Some(c) = self.getc()? {
-            match c {
-                b'{'=> {
-                    self.read_object()?;
-                    self.emit(JsonType::Object);
-                },
-                b'[' => {
-                    self.read_array()?;
-                    self.emit(JsonType::Array);
-                },
-                b'0' ..= b'9' | b'-' => {
-                    self.ungetc(c);
-                    self.read_number()?;
-                    self.emit(JsonType::Number);
-                },
-                b'"' => {
-                    self.value.clear();
-                    self.read_string(false)?;
-                    self.emit(JsonType::String);
-                },
-                b't' => {
-                    self.read_literal(b"rue")?;
-                    self.emit(JsonType::True);
-                },
-                b'f' => {
-                    self.read_literal(b"alse")?;
-                    self.emit(JsonType::False);
+    /// The number of bytes of input parsed so far. Bytes read ahead, such as
+    /// the one that ended a number, are not counted until they are parsed.
+    pub fn bytes_consumed(&self) -> usize {
+        self.offset
+    }
+
+    /// Counts the values read so far, including values that are not
+    /// reported because of a filter. Reset by [`reset`](Self::reset).
+    pub fn stats(&self) -> ParseStats {
+        self.stats
+    }
+
+    /// Parses the input, reporting every value to the callback.
+    pub fn parse(&mut self) -> Result<(), JsonError> {
+        while let Some(typ) = self.next_event()? {
+            if self.report(typ).is_break() {
+                return Ok(());
+            }
+        }
+
+        if self.options.strict_trailing {
+            self.finish()?;
+        }
+        self.complete();
+        Ok(())
+    }
+
+    /// Parses the input like [`parse`](Self::parse), but carries on after
+    /// syntax errors and returns all of them, in order.
+    ///
+    /// After an error inside an array or object, the rest of the value is
+    /// skipped up to the next `,`, `]` or `}` of that container, stepping
+    /// over strings and nested containers, and parsing resumes there. A
+    /// string is first skipped to its closing quote when the error was
+    /// inside it. A bracket that closes an outer container closes the inner
+    /// ones too, which then get no end event. Values that could not be read
+    /// are not reported. Parsing stops at an error in a top-level scalar, at
+    /// the end of the input and at errors from the reader or from limits
+    /// such as the maximum depth.
+    pub fn parse_recovering(&mut self) -> Vec<JsonError> {
+        let mut errors = Vec::new();
+        loop {
+            match self.next_event() {
+                Ok(Some(typ)) => {
+                    if self.report(typ).is_break() {
+                        return errors;
+                    }
                 },
-                b'n' => {
-                    self.read_literal(b"ull")?;
-                    self.emit(JsonType::Null);
+                Ok(None) => break,
+                Err(e) => {
+                    let recovered = self.recover(&e);
+                    errors.push(e);
+                    match recovered {
+                        Ok(true) => {
+                            if self.subtree_skip.is_some_and(|depth| self.stack.len() <= depth) {
+                                self.end_skip();
+                            }
+                            continue;
+                        },
+                        Ok(false) => return errors,
+                        Err(e) => {
+                            errors.push(e);
+                            return errors;
+                        },
+                    }
                 },
-                b' ' | b'\t' | b'\r' | b'\n' => continue,
-                _ => return Err("unexpected char".into()),
             }
         }
-        Ok(())
+
+        if self.options.strict_trailing && let Err(e) = self.finish() {
+            errors.push(e);
+        } else {
+            self.complete();
+        }
+        errors
     }
 
-    fn read_number(&mut self) -> Result<(), String> {
-        self.value.clear();
+    /// Skips ahead after `error` to where the innermost container can go
+    /// on, see [`parse_recovering`](Self::parse_recovering). Returns false
+    /// when parsing cannot go on.
+    fn recover(&mut self, error: &JsonError) -> Result<bool, JsonError> {
+        let fatal = matches!(error,
+            JsonError::Io { .. }
+            | JsonError::UnexpectedEof { .. }
+            | JsonError::UnterminatedString { .. }
+            | JsonError::DepthExceeded { .. }
+            | JsonError::InputTooLarge { .. }
+            | JsonError::TooManyElements { .. }
+            | JsonError::TooManyMembers { .. }
+            | JsonError::Cancelled { .. });
+        if fatal || self.stack.is_empty() {
+            return Ok(false);
+        }
 
-        while let Some(c) = self.getc()? {
-            if !(c >= b'0' && c <= b'9') && c != b'.' && c != b'-' && c != b'+' && c != b'e' && c != b'E' {
-                self.ungetc(c);
-                break;
-            } else {
-                self.value.push(c as char);
+        if self.in_string {
+            self.in_string = false;
+            while let Some(c) = self.getc()? {
+                match c {
+                    b'\\' => {
+                        self.getc()?;
+                    },
+                    b'"' => break,
+                    _ => {},
+                }
             }
+        } else if matches!(error, JsonError::UnexpectedChar { .. })
+            && self.ungets.is_empty()
+            && matches!(self.last, b',' | b']' | b'}' | b'"')
+        {
+            // The unexpected byte may well start what comes next.
+            self.ungetc(self.last);
         }
 
-        Ok(())
+        while let Some(c) = self.skip_to_boundary()? {
+            let object = c == b'}';
+            if c == b',' {
+                let frame = self.stack.last_mut().expect("checked above");
+                frame.index += 1;
+                frame.state = State::AfterComma;
+                self.path.truncate(frame.path_len);
+                return Ok(true);
+            }
+            let Some(outer) = self.stack.iter().rposition(|f| f.object == object) else {
+                continue;
+            };
+            // A bracket closing an outer container also closes the ones
+            // inside it, without end events.
+            while self.stack.len() > outer + 1 {
+                let frame = self.stack.pop().expect("deeper than outer");
+                self.path.truncate(frame.path_len);
+                if let Some(key) = frame.key {
+                    self.key = key;
+                }
+            }
+            self.stack[outer].state = State::AfterValue;
+            self.ungetc(c);
+            return Ok(true);
+        }
+        Ok(true)
     }
 
-    fn read_string(&mut self, path: bool) -> Result<usize, String> {
-        let mut in_escape = false;
-        let mut i: usize = 0;
-        
+    /// Reads up to the next `,`, `]` or `}` outside of strings and nested
+    /// containers.
+    fn skip_to_boundary(&mut self) -> Result<Option<u8>, JsonError> {
+        let mut depth = 0;
+        let mut in_string = false;
         while let Some(c) = self.getc()? {
-            match c {
-                b'\\' => {
-                    in_escape = true;
+            match (in_string, c) {
+                (true, b'\\') => {
+                    self.getc()?;
                 },
-                b'"' if !in_escape => {
-                    return Ok(i);
-                },
-                _ => {
-                    if in_escape {
-                        in_escape = false;
-                    }
-                    if path {
-                        self.path.push(c as char);
-                    } else {
-                        self.value.push(c as char);
-                    }
-                    i += 1;
-                }
+                (true, b'"') | (false, b'"') => in_string = !in_string,
+                (true, _) => {},
+                (false, b'[' | b'{') => depth += 1,
+                (false, b']' | b'}') if depth > 0 => depth -= 1,
+                (false, b',' | b']' | b'}') if depth == 0 => return Ok(Some(c)),
+                _ => {},
             }
         }
-        Err("unterminated string".into())
+        Ok(None)
     }
 
-    fn read_literal(&mut self, s: &[u8]) -> Result<(), String> {
-        for i in 0..s.len() {
-            let b = self.getc()?;
-            
-            if let Some(x) = b {
-                if x != s[i] {
-                    return Err(format!("expected '{}' but got '{}'", s[i] as char, x as char));
+    /// Reads the next value, of any type, without reporting it or the
+    /// values inside of it, and without calling the key and string chunk
+    /// hooks. Inside an object
+    /// the key of the member is skipped too.
+    ///
+    /// Fails if the top-level value has already been read, or if the
+    /// innermost container ends before another value.
+    pub fn skip_value(&mut self) -> Result<(), JsonError> {
+        self.skipping = true;
+        let result = self.skip_next(self.stack.len());
+        self.skipping = false;
+        result
+    }
+
+    fn skip_next(&mut self, depth: usize) -> Result<(), JsonError> {
+        loop {
+            let between = self.stack.last().is_some_and(|f| matches!(f.state, State::First | State::AfterValue));
+            if between
+                && self.stack.len() == depth
+                && let Some(c @ (b']' | b'}')) = self.peek_token()?
+            {
+                return Err(JsonError::UnexpectedChar {
+                    msg: format!("expected a value to skip but found {}", show(c)),
+                    pos: self.pos(),
+                });
+            }
+            if self.next_event()?.is_none() {
+                return Err(JsonError::UnexpectedEof { msg: "no value left to skip".into(), pos: self.pos() });
+            }
+            if self.stack.len() == depth {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Parses newline-delimited JSON, where every line holds one value.
+    ///
+    /// Each record is reported with paths rooted at `$`. Blank lines are
+    /// skipped and errors are wrapped in [`JsonError::Record`].
+    pub fn parse_lines(&mut self) -> Result<(), JsonError> {
+        let mut record = 0;
+        self.skip_bom()?;
+
+        while self.peek_token()?.is_some() {
+            record += 1;
+            self.started = false;
+            let flow = self.read_record()
+                .map_err(|e| JsonError::Record { record, error: Box::new(e) })?;
+            if flow.is_break() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a stream of values separated by optional whitespace, such as
+    /// `1 2 [3]`, until the end of the input.
+    ///
+    /// Each value is reported with paths rooted at `$`. An empty input holds
+    /// no values and is not an error.
+    pub fn parse_stream(&mut self) -> Result<(), JsonError> {
+        self.skip_bom()?;
+
+        while self.peek_token()?.is_some() {
+            self.started = false;
+            while let Some(typ) = self.next_event()? {
+                if self.report(typ).is_break() {
+                    return Ok(());
                 }
-            } else {
-                return Err(format!("unexpected end instead of '{}'", s[i] as char));
             }
+            self.complete();
         }
-        
         Ok(())
     }
 
-    fn read_array(&mut self) -> Result<(), String> {
-        let mut reading_value = true;
-        let mut i: usize = 0;
-        
+    /// Parses the next top-level value of a stream such as `1 2 [3]`,
+    /// skipping the whitespace before it. Returns false, without reporting
+    /// anything, when only whitespace is left.
+    ///
+    /// When the callback returns `ControlFlow::Break` inside an array or
+    /// object, the next call carries on with the rest of that value.
+    pub fn parse_next(&mut self) -> Result<bool, JsonError> {
+        if self.stack.is_empty() {
+            if self.offset == 0 {
+                self.skip_bom()?;
+            }
+            if self.peek_token()?.is_none() {
+                return Ok(false);
+            }
+            self.started = false;
+        }
+
+        while let Some(typ) = self.next_event()? {
+            if self.report(typ).is_break() {
+                return Ok(true);
+            }
+        }
+        self.complete();
+        Ok(true)
+    }
+
+    fn read_record(&mut self) -> Result<ControlFlow<()>, JsonError> {
+        while let Some(typ) = self.next_event()? {
+            if self.report(typ).is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+
         while let Some(c) = self.getc()? {
             match c {
-                b']' => return Ok(()),
-                b',' => {
-                    i += 1;
-                    reading_value = true;
+                b'\n' => break,
+                b' ' | b'\t' | b'\r' => continue,
+                0x0B | 0x0C if self.options.lenient_whitespace => continue,
+                b'/' if self.options.allow_comments => self.skip_comment()?,
+                _ => return Err(self.letter_after_number(c).unwrap_or_else(|| JsonError::UnexpectedChar {
+                    msg: format!("expected a newline after the record but found {}", show(c)),
+                    pos: self.pos(),
+                })),
+            }
+        }
+        self.complete();
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn complete(&mut self) {
+        if let Some(on_complete) = &mut self.hooks.on_complete {
+            on_complete();
+        }
+    }
+
+    /// Checks that nothing but whitespace follows the top-level value.
+    fn finish(&mut self) -> Result<(), JsonError> {
+        match self.skip_whitespace()? {
+            Some(c) => Err(self.letter_after_number(c).unwrap_or(JsonError::TrailingData { pos: self.pos() })),
+            None => Ok(()),
+        }
+    }
+
+    fn report(&mut self, typ: JsonType) -> ControlFlow<()> {
+        if self.options.filter.as_ref().is_some_and(|f| !path::matches(f, &self.path, self.options.separator)) {
+            return ControlFlow::Continue(());
+        }
+        if self.options.subtree.as_ref().is_some_and(|s| !path::is_within(s, &self.path, self.options.separator)) {
+            return ControlFlow::Continue(());
+        }
+        let key = self.event_member.then_some(self.key.as_str());
+        let mut segments = recycle(std::mem::take(&mut self.segments));
+        if self.options.path_segments {
+            for frame in &self.stack[1.min(self.event_depth)..self.event_depth] {
+                match (&frame.key, frame.element) {
+                    (Some(key), _) => segments.push(PathSegment::Key(key)),
+                    (None, Some(i)) => segments.push(PathSegment::Index(i)),
+                    (None, None) => {},
+                }
+            }
+            match (key, self.event_index) {
+                (Some(key), _) => segments.push(PathSegment::Key(key)),
+                (None, Some(i)) => segments.push(PathSegment::Index(i)),
+                (None, None) => {},
+            }
+        }
+        let event = Event {
+            path: &self.path,
+            typ,
+            value: self.borrowed.unwrap_or(&self.value),
+            offset: self.event_offset,
+            key,
+            index: self.event_index,
+            parent: match (self.event_member, self.event_index) {
+                (true, _) => Parent::ObjectMember,
+                (false, Some(_)) => Parent::ArrayElement,
+                (false, None) => Parent::Root,
+            },
+            depth: self.event_depth,
+            empty: self.event_empty,
+            len: self.event_len,
+            segments: &segments,
+        };
+        if let Some(on_path) = self.hooks.on_path.get_mut(self.path.as_str()) {
+            for on_path in on_path {
+                on_path(&event);
+            }
+        }
+        let flow = (self.on_value)(&event);
+        self.segments = recycle(segments);
+        flow
+    }
+
+    fn skip_bom(&mut self) -> Result<(), JsonError> {
+        let mark = self.mark();
+        match self.getc()? {
+            Some(0xEF) => {
+                let bom = self.getc().and_then(|c| Ok(c == Some(0xBB) && self.getc()? == Some(0xBF)));
+                if !bom.map_err(|e| self.suspended(e, None, Some(mark)))? {
+                    return Err(JsonError::UnexpectedChar { msg: "invalid byte order mark".into(), pos: self.pos() });
+                }
+                self.column = 0;
+            },
+            Some(c) => self.ungetc(c),
+            None => {},
+        }
+        Ok(())
+    }
+
+    fn skip_comment(&mut self) -> Result<(), JsonError> {
+        let mark = self.mark_before();
+        match self.getc().map_err(|e| self.suspended(e, None, Some(mark)))? {
+            Some(b'/') => self.continue_comment(0, 0),
+            Some(b'*') => self.continue_comment(1, 0),
+            _ => Err(JsonError::UnexpectedChar { msg: "expected '/' or '*' after '/'".into(), pos: self.pos() }),
+        }
+    }
+
+    /// Reads the rest of a comment, a line comment when `depth` is 0.
+    fn continue_comment(&mut self, mut depth: usize, mut prev: u8) -> Result<(), JsonError> {
+        loop {
+            let c = self.getc().map_err(|e| self.suspended(e, Some(Partial::Comment { depth, prev }), None))?;
+            match (depth, c) {
+                (0, Some(b'\n')) => {
+                    self.ungetc(b'\n');
+                    return Ok(());
                 },
-                b' ' | b'\t' | b'\r' | b'\n' => continue,
-                _ => {
-                    if !reading_value {
-                        return Err(format!("invalid char '{}'", c));
+                (0, Some(_)) => {},
+                (0, None) => return Ok(()),
+                (_, Some(c)) => {
+                    match (prev, c) {
+                        (b'*', b'/') => {
+                            depth -= 1;
+                            if depth == 0 {
+                                return Ok(());
+                            }
+                            prev = 0;
+                        },
+                        (b'/', b'*') => {
+                            depth += 1;
+                            prev = 0;
+                        },
+                        _ => prev = c,
                     }
+                },
+                (_, None) => {
+                    return Err(JsonError::UnexpectedEof { msg: "unterminated block comment".into(), pos: self.pos() });
+                },
+            }
+        }
+    }
+
+    fn pos(&self) -> Position {
+        Position { offset: self.offset, line: self.line, column: self.column, path: self.path.clone() }
+    }
+
+    fn mark(&self) -> Mark {
+        Mark { offset: self.offset, line: self.line, column: self.column, prev_column: self.prev_column }
+    }
 
-                    self.ungetc(c);
-                    let l = self.path.len();
-                    self.path.push('[');
-                    self.path.push_str(&i.to_string());
-                    self.path.push(']');
+    /// The position of the byte just read, which is not a newline.
+    fn mark_before(&self) -> Mark {
+        Mark { offset: self.offset - 1, column: self.column - 1, ..self.mark() }
+    }
 
-                    self.read_value()?;
+    /// Passes on `error`, first noting what to carry on with when it only
+    /// means that the input ran dry. The innermost call gets to say.
+    fn suspended(&mut self, error: JsonError, partial: Option<Partial>, rewind: Option<Mark>) -> JsonError {
+        if would_block(&error) {
+            self.partial = self.partial.or(partial);
+            self.rewind = self.rewind.or(rewind);
+        }
+        error
+    }
 
-                    self.path.truncate(l);
-                    reading_value = false;
+    fn getc(&mut self) -> Result<Option<u8>, JsonError> {
+        let c = match self.ungets.pop() {
+            Some(u) => u,
+            None => {
+                if self.input_pos == self.input_len && !self.fill()? {
+                    return Ok(None);
+                }
+                self.bytes_read += 1;
+                if let Some(max) = self.options.max_total_bytes.filter(|&max| self.bytes_read > max) {
+                    return Err(JsonError::InputTooLarge { max, pos: self.pos() });
+                }
+                if self.bytes_read >= self.next_cancel_check {
+                    self.next_cancel_check = self.bytes_read + CANCEL_CHECK_INTERVAL;
+                    if self.options.cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed)) {
+                        return Err(JsonError::Cancelled { pos: self.pos() });
+                    }
                 }
+                self.input_pos += 1;
+                self.input[self.input_pos - 1]
+            },
+        };
+
+        self.offset += 1;
+        self.last = c;
+        if c == b'\n' {
+            self.line += 1;
+            self.prev_column = self.column;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+        Ok(Some(c))
+    }
+
+    fn fill(&mut self) -> Result<bool, JsonError> {
+        let Cow::Owned(input) = &mut self.input else {
+            return Ok(false);
+        };
+        loop {
+            match self.reader.read(input) {
+                Ok(n) => {
+                    self.input_pos = 0;
+                    self.input_len = n;
+                    return Ok(n > 0);
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(JsonError::Io { error, pos: self.pos() }),
             }
         }
-        Err("unexpected end of array".into())
     }
 
-    fn read_object(&mut self) -> Result<(), String> {
-        let mut key_len = 0;
-        self.path.push('.');
-        
+    fn ungetc(&mut self, c: u8) {
+        self.offset -= 1;
+        if c == b'\n' {
+            self.line -= 1;
+            self.column = self.prev_column;
+        } else {
+            self.column -= 1;
+        }
+        self.ungets.push(c);
+    }
+
+    /// Skips whitespace, and comments when they are allowed, returning the
+    /// byte that follows.
+    fn skip_whitespace(&mut self) -> Result<Option<u8>, JsonError> {
         while let Some(c) = self.getc()? {
             match c {
-                b'}' => {
-                    self.path.truncate(self.path.len() - key_len - 1);
-                    return Ok(());
-                },
-                b'"' => {
-                    if key_len != 0 {
-                        return Err(format!("expecting a ':' after '{}'", self.path));
+                b' ' | b'\t' | b'\r' | b'\n' => continue,
+                0x0B | 0x0C if self.options.lenient_whitespace => continue,
+                b'/' if self.options.allow_comments => self.skip_comment()?,
+                _ => return Ok(Some(c)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Skips whitespace and comments like `skip_whitespace`, but leaves the
+    /// next byte to be read again.
+    fn peek_token(&mut self) -> Result<Option<u8>, JsonError> {
+        let c = self.skip_whitespace()?;
+        if let Some(c) = c {
+            self.ungetc(c);
+        }
+        Ok(c)
+    }
+
+    /// Reads up to the next event, leaving its path, value and key in the
+    /// parser. Returns `None` once the top-level value has been read, and
+    /// fails if there is no value at all.
+    ///
+    /// Open containers are kept on `stack` rather than in recursive calls,
+    /// so reading can stop after any event and pick up from there later.
+    fn next_event(&mut self) -> Result<Option<JsonType>, JsonError> {
+        loop {
+            let event = match (self.partial.take(), self.stack.last()) {
+                (Some(partial), _) => self.resume(partial)?,
+                (None, Some(frame)) => {
+                    let (object, state) = (frame.object, frame.state);
+                    let c = self.skip_whitespace()?;
+                    if object {
+                        self.object_token(state, c)?
+                    } else {
+                        self.array_token(state, c)?
                     }
-                    
-                    key_len = self.read_string(true)?;
                 },
-                b':' => {
-                    if key_len == 0 {
-                        return Err("expecting a key before ':'".into());
+                (None, None) if self.started => return Ok(None),
+                (None, None) => {
+                    if self.offset == 0 {
+                        self.skip_bom()?;
                     }
-                    self.read_value()?;
-                },
-                b',' => {
-                    self.path.truncate(self.path.len() - key_len);
-                    key_len = 0;
+                    let Some(c) = self.skip_whitespace()? else {
+                        return Err(JsonError::UnexpectedEof { msg: "empty input: expected a value".into(), pos: self.pos() });
+                    };
+                    self.started = true;
+                    self.begin_value(c, false)?
                 },
-                b' ' | b'\t' | b'\r' | b'\n' => continue,
-                _ => return Err("unexpected char".into())
+            };
+
+            if let Some(depth) = self.subtree_skip {
+                if event.is_some() && self.stack.len() == depth {
+                    self.end_skip();
+                }
+            } else if event.is_some() {
+                return Ok(event);
             }
         }
-        Err("unexpected end of object".into())
     }
-}
 
-pub fn json_parse<T, F>(input: T, on_value: F) -> Result<(), String>
-where
-    T: std::io::Read, 
-    F: FnMut(&str, JsonType, &str)
-{
-    JsonParser::new(input, on_value).read_value()
-}
+    /// Carries on with the token the input ran dry in.
+    fn resume(&mut self, partial: Partial) -> Result<Option<JsonType>, JsonError> {
+        match partial {
+            Partial::Value { c, member } => self.begin_value(c, member),
+            Partial::String { key: true, quote } => {
+                self.continue_string(true, quote)?;
+                self.end_key()?;
+                Ok(None)
+            },
+            Partial::String { key: false, quote } => {
+                self.continue_string(false, quote)?;
+                Ok(Some(self.count_scalar(JsonType::String)))
+            },
+            Partial::Number => {
+                let typ = self.continue_number()?;
+                Ok(Some(self.count_scalar(typ)))
+            },
+            Partial::Comment { depth, prev } => {
+                self.continue_comment(depth, prev)?;
+                Ok(None)
+            },
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Skips the value about to be read, which is outside the subtree, up
+    /// to the next event at the current depth.
+    fn skip_outside(&mut self) {
+        self.subtree_skip = Some(self.stack.len());
+        self.skipping = true;
+    }
 
-    #[test]
-    fn test_number() {
-        let data = b"1234.56";
-        json_parse(data.as_slice(), |p, t, v| {
-            assert_eq!("$", p);
-            assert_eq!(JsonType::Number, t);
-            assert_eq!("1234.56", v);
-        }).expect("failed to parse");
+    fn end_skip(&mut self) {
+        self.subtree_skip = None;
+        self.skipping = false;
+    }
+
+    /// Starts the value beginning with `c`. Scalars are read whole, arrays
+    /// and objects get a frame on the stack. Returns the event to report,
+    /// if there is one yet.
+    fn begin_value(&mut self, c: u8, member: bool) -> Result<Option<JsonType>, JsonError> {
+        self.event_offset = self.offset - 1;
+        self.event_member = member;
+        self.event_index = self.stack.last().filter(|f| !f.object).map(|f| f.index);
+        self.event_depth = self.stack.len();
+        self.event_empty = false;
+        self.event_len = 0;
+        self.value.clear();
+        self.borrowed = None;
+
+        let typ = match c {
+            b'{' | b'[' => {
+                if self.stack.len() == self.options.max_depth {
+                    return Err(JsonError::DepthExceeded { max: self.options.max_depth, pos: self.pos() });
+                }
+                if c == b'{' {
+                    self.stats.objects += 1;
+                } else {
+                    self.stats.arrays += 1;
+                }
+                self.stats.max_depth = self.stats.max_depth.max(self.stack.len() + 1);
+                self.stack.push(Frame {
+                    object: c == b'{',
+                    state: State::First,
+                    index: 0,
+                    path_len: self.path.len(),
+                    start: self.event_offset,
+                    key: member.then(|| self.key.clone()),
+                    element: self.event_index,
+                    seen: HashSet::new(),
+                });
+                match (c, self.options.container_events) {
+                    (_, false) => return Ok(None),
+                    (b'{', true) => JsonType::ObjectStart,
+                    _ => JsonType::ArrayStart,
+                }
+            },
+            b'0' ..= b'9' | b'-' => {
+                self.ungetc(c);
+                self.read_number()?
+            },
+            b'"' => {
+                self.read_string(false, c)?;
+                JsonType::String
+            },
+            b'\'' if self.options.single_quotes => {
+                self.read_string(false, c)?;
+                JsonType::String
+            },
+            b'\'' => return Err(JsonError::UnexpectedChar {
+                msg: "strings must use double quotes, not single quotes".into(),
+                pos: self.pos(),
+            }),
+            b't' => {
+                self.read_value_literal(c, member, b"rue")?;
+                JsonType::True
+            },
+            b'f' => {
+                self.read_value_literal(c, member, b"alse")?;
+                JsonType::False
+            },
+            b'n' => {
+                self.read_value_literal(c, member, b"ull")?;
+                JsonType::Null
+            },
+            b'N' if self.options.allow_nan => {
+                self.read_value_literal(c, member, b"aN")?;
+                self.value.push_str("NaN");
+                JsonType::Number
+            },
+            b'I' if self.options.allow_nan => {
+                self.read_value_literal(c, member, b"nfinity")?;
+                self.value.push_str("Infinity");
+                JsonType::Number
+            },
+            _ => return Err(self.letter_after_number(c).unwrap_or_else(|| JsonError::UnexpectedChar {
+                msg: format!("expected a value but found {}", show(c)),
+                pos: self.pos(),
+            })),
+        };
+        Ok(Some(self.count_scalar(typ)))
+    }
+
+    fn count_scalar(&mut self, typ: JsonType) -> JsonType {
+        match typ {
+            JsonType::String => self.stats.strings += 1,
+            JsonType::Number | JsonType::Integer => self.stats.numbers += 1,
+            JsonType::True | JsonType::False => self.stats.booleans += 1,
+            JsonType::Null => self.stats.nulls += 1,
+            _ => {},
+        }
+        typ
+    }
+
+    /// Reads the `rest` of the literal value starting with `c`.
+    fn read_value_literal(&mut self, c: u8, member: bool, rest: &[u8]) -> Result<(), JsonError> {
+        let mark = self.mark();
+        self.read_literal(rest).map_err(|e| self.suspended(e, Some(Partial::Value { c, member }), Some(mark)))
+    }
+
+    /// Pops the innermost container and sets up its end event.
+    fn close_container(&mut self) -> JsonType {
+        let frame = self.stack.pop().expect("closing without an open container");
+        self.path.truncate(frame.path_len);
+        self.value.clear();
+        self.borrowed = None;
+        self.event_offset = frame.start;
+        self.event_member = frame.key.is_some();
+        self.event_index = frame.element;
+        self.event_depth = self.stack.len();
+        self.event_empty = frame.state == State::First;
+        self.event_len = if self.event_empty { 0 } else { frame.index + 1 };
+        if let Some(key) = frame.key {
+            self.key = key;
+        }
+
+        match (frame.object, self.options.container_events) {
+            (true, true) => JsonType::ObjectEnd,
+            (true, false) => JsonType::Object,
+            (false, true) => JsonType::ArrayEnd,
+            (false, false) => JsonType::Array,
+        }
+    }
+
+    /// Names `c`, just read, as a letter right after a number, as in
+    /// `42abc`, rather than as whatever was expected after the number.
+    fn letter_after_number(&self, c: u8) -> Option<JsonError> {
+        (c.is_ascii_alphabetic() && self.number_end == Some(self.offset - 1)).then(|| JsonError::UnexpectedChar {
+            msg: format!("unexpected {} after number", show(c)),
+            pos: self.pos(),
+        })
     }
-}
\ No newline at end of file
+
+    fn read_number(&mut self) -> Result<JsonType, JsonError> {
+        self.value.clear();
+        self.continue_number()
+    }
+
+    /// Reads the rest of a number, after the text already in `value`.
+    fn continue_number(&mut self) -> Result<JsonType, JsonError> {
+        while let Some(c) = self.getc().map_err(|e| self.suspended(e, Some(Partial::Number), None))? {
+            let underscore = c == b'_' && self.options.number_underscores;
+            if !c.is_ascii_digit() && c != b'.' && c != b'-' && c != b'+' && c != b'e' && c != b'E' && !underscore {
+                self.ungetc(c);
+                break;
+            } else {
+                self.value.push(c as char);
+            }
+            if let Some(max) = self.options.max_value_len.filter(|&max| self.value.len() > max) {
+                return Err(JsonError::ValueTooLong { max, pos: self.pos() });
+            }
+        }
+
+        if self.options.allow_nan && self.value == "-" {
+            let mark = self.mark();
+            let partial = Partial::Value { c: b'-', member: self.event_member };
+            self.read_literal(b"Infinity").map_err(|e| self.suspended(e, Some(partial), Some(mark)))?;
+            self.value.push_str("Infinity");
+            return Ok(JsonType::Number);
+        }
+
+        if let Some(on_number_text) = self.hooks.on_number_text.as_mut().filter(|_| !self.skipping) {
+            let text = on_number_text(&self.value);
+            if !std::ptr::eq(text.as_ref(), self.value.as_str()) {
+                self.value = text.into_owned();
+            }
+        }
+
+        if self.value.contains('_') {
+            let b = self.value.as_bytes();
+            let between_digits = |i: usize| i > 0 && b[i - 1].is_ascii_digit() && b.get(i + 1).is_some_and(u8::is_ascii_digit);
+            if b.iter().enumerate().any(|(i, &c)| c == b'_' && !between_digits(i)) {
+                return Err(JsonError::InvalidNumber {
+                    msg: format!("invalid number '{}': '_' must be between digits", self.value),
+                    pos: self.pos(),
+                });
+            }
+            self.value.retain(|c| c != '_');
+        }
+
+        check_number(&self.value)
+            .map_err(|msg| JsonError::InvalidNumber { msg, pos: self.pos() })?;
+
+        self.number_end = Some(self.offset);
+        if self.value.contains(['.', 'e', 'E']) {
+            Ok(JsonType::Number)
+        } else {
+            Ok(JsonType::Integer)
+        }
+    }
+
+    /// Reads a string up to the closing `quote`, which is `"` unless
+    /// single quotes are allowed.
+    fn read_string(&mut self, key: bool, quote: u8) -> Result<(), JsonError> {
+        self.buf.clear();
+        self.string_len = 0;
+        self.continue_string(key, quote)
+    }
+
+    /// Reads the rest of a string, after the bytes already in `buf`.
+    fn continue_string(&mut self, key: bool, quote: u8) -> Result<(), JsonError> {
+        let result = self.decode_string(key, quote);
+        self.in_string = result.is_err() && self.last != quote;
+        result.map_err(|e| self.suspended(e, Some(Partial::String { key, quote }), None))
+    }
+
+    fn decode_string(&mut self, key: bool, quote: u8) -> Result<(), JsonError> {
+        let chunked = !key && !self.skipping && self.hooks.on_chunk.is_some();
+        let max = self.options.max_string_len.unwrap_or(usize::MAX);
+        let max_value = self.options.max_value_len.filter(|_| !key).unwrap_or(usize::MAX);
+        let limit = max.min(max_value);
+        let raw = !key && self.options.raw_strings;
+
+        if !key && !chunked && !self.options.lossy_strings && quote == b'"' && self.string_len == 0
+            && self.ungets.is_empty() && let Cow::Borrowed(data) = self.input
+        {
+            let start = self.input_pos;
+            loop {
+                let n = self.plain_run(limit - (self.input_pos - start), quote);
+                self.skip_plain(n);
+                match (data.get(self.input_pos), data.get(self.input_pos + 1)) {
+                    (Some(b'"'), _) => {
+                        let run = &data[start..self.input_pos];
+                        self.getc()?;
+                        let s = std::str::from_utf8(run).map_err(|_| JsonError::InvalidUtf8 { pos: self.pos() })?;
+                        self.borrowed = Some(s);
+                        return Ok(());
+                    },
+                    (Some(b'\\'), Some(b'"' | b'\\')) if raw && self.input_pos - start + 2 <= limit => {
+                        self.getc()?;
+                        self.getc()?;
+                    },
+                    (Some(b'\\'), Some(_)) if raw && self.input_pos - start < limit => {
+                        self.getc()?;
+                    },
+                    _ => break,
+                }
+            }
+            self.buf.extend_from_slice(&data[start..self.input_pos]);
+            self.string_len = self.input_pos - start;
+        }
+
+        loop {
+            if !chunked {
+                let n = self.plain_run(limit - self.string_len, quote);
+                self.buf.extend_from_slice(&self.input[self.input_pos..self.input_pos + n]);
+                self.skip_plain(n);
+                self.string_len += n;
+            }
+            let Some(c) = self.getc()? else {
+                break;
+            };
+            match c {
+                _ if c == quote && chunked => return self.flush_chunk(true),
+                _ if c == quote => {
+                    if self.options.lossy_strings {
+                        replace_invalid_utf8(&mut self.buf, true);
+                    }
+                    let s = std::str::from_utf8(&self.buf)
+                        .map_err(|_| JsonError::InvalidUtf8 { pos: self.pos() })?;
+                    if key {
+                        self.key.clear();
+                        self.key.push_str(s);
+                    } else if !self.skipping {
+                        self.value.push_str(s);
+                    }
+                    return Ok(());
+                },
+                b'\\' if raw => {
+                    // Only an escaped quote or backslash needs looking at, to
+                    // find the end of the string.
+                    let mark = self.mark_before();
+                    let next = self.getc().map_err(|e| self.suspended(e, None, Some(mark)))?;
+                    self.buf.push(c);
+                    self.string_len += 1;
+                    match next {
+                        Some(c) if c == quote || c == b'\\' => {
+                            self.buf.push(c);
+                            self.string_len += 1;
+                        },
+                        Some(c) => self.ungetc(c),
+                        None => break,
+                    }
+                },
+                b'\\' => {
+                    let mark = self.mark_before();
+                    let c = match self.read_escape() {
+                        Err(JsonError::InvalidEscape { .. }) if self.options.lossy_strings => char::REPLACEMENT_CHARACTER,
+                        c => c.map_err(|e| self.suspended(e, None, Some(mark)))?,
+                    };
+                    self.buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    self.string_len += c.len_utf8();
+                },
+                0x00..=0x1f => return Err(JsonError::UnexpectedChar {
+                    msg: format!("unescaped control character 0x{:02x} in string", c),
+                    pos: self.pos(),
+                }),
+                _ => {
+                    self.buf.push(c);
+                    self.string_len += 1;
+                },
+            }
+
+            if self.string_len > max {
+                return Err(JsonError::StringTooLong { max, pos: self.pos() });
+            }
+            if self.string_len > max_value {
+                return Err(JsonError::ValueTooLong { max: max_value, pos: self.pos() });
+            }
+            if chunked {
+                self.flush_chunk(false)?;
+            }
+        }
+        Err(JsonError::UnterminatedString { pos: self.pos() })
+    }
+
+    /// Counts the bytes at the read position that can be copied into a
+    /// string as they are, up to `max` of them: those in the buffer, when
+    /// there is nothing to read again, up to the closing `quote`, a
+    /// backslash or a control character.
+    fn plain_run(&self, max: usize, quote: u8) -> usize {
+        if !self.ungets.is_empty() {
+            return 0;
+        }
+        let limit = self.options.max_total_bytes.map_or(usize::MAX, |m| m.saturating_sub(self.bytes_read));
+        let rest = &self.input[self.input_pos..self.input_len];
+        let rest = &rest[..rest.len().min(max).min(limit)];
+        rest.iter().position(|&c| c == quote || c == b'\\' || c < 0x20).unwrap_or(rest.len())
+    }
+
+    /// Moves past `n` bytes counted by `plain_run`, as `getc` would.
+    fn skip_plain(&mut self, n: usize) {
+        if n > 0 {
+            self.input_pos += n;
+            self.bytes_read += n;
+            self.offset += n;
+            self.column += n;
+            self.last = self.input[self.input_pos - 1];
+        }
+    }
+
+    /// Passes the decoded bytes of a string to the chunk hook once there
+    /// are enough of them, or all of them at the end of the string. An
+    /// incomplete UTF-8 sequence at the end is kept for the next chunk.
+    fn flush_chunk(&mut self, last: bool) -> Result<(), JsonError> {
+        let Some((size, _)) = self.hooks.on_chunk else {
+            return Ok(());
+        };
+        if !last && self.buf.len() < size {
+            return Ok(());
+        }
+        if self.options.lossy_strings {
+            replace_invalid_utf8(&mut self.buf, last);
+        }
+
+        let valid = match std::str::from_utf8(&self.buf) {
+            Ok(s) => s.len(),
+            Err(e) if !last && e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err(JsonError::InvalidUtf8 { pos: self.pos() }),
+        };
+        if valid == 0 && !last {
+            return Ok(());
+        }
+
+        let chunk = std::str::from_utf8(&self.buf[..valid]).expect("validated above");
+        if let Some((_, on_chunk)) = &mut self.hooks.on_chunk {
+            on_chunk(&self.path, chunk, last);
+        }
+        self.buf.drain(..valid);
+        Ok(())
+    }
+
+    fn read_escape(&mut self) -> Result<char, JsonError> {
+        match self.getc()? {
+            Some(b'"') => Ok('"'),
+            Some(b'\\') => Ok('\\'),
+            Some(b'/') => Ok('/'),
+            Some(b'b') => Ok('\u{8}'),
+            Some(b'f') => Ok('\u{c}'),
+            Some(b'n') => Ok('\n'),
+            Some(b'r') => Ok('\r'),
+            Some(b't') => Ok('\t'),
+            Some(b'u') => self.read_unicode_escape(),
+            Some(b'0') if self.options.allow_nul_escape => Ok('\0'),
+            Some(b'\'') if self.options.single_quotes => Ok('\''),
+            Some(c) => Err(JsonError::InvalidEscape { msg: format!("invalid escape '\\{}'", c as char), pos: self.pos() }),
+            None => Err(JsonError::UnterminatedString { pos: self.pos() }),
+        }
+    }
+
+    fn read_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let invalid = |msg, pos| JsonError::InvalidEscape { msg, pos };
+
+        let u = match self.read_hex4()? {
+            h @ 0xD800..=0xDBFF => {
+                let unpaired = |p: &mut Self| invalid(format!("unpaired high surrogate '\\u{:04X}'", h), p.pos());
+                match self.getc()? {
+                    Some(b'\\') => {},
+                    next => {
+                        self.unget_lossy(next);
+                        return Err(unpaired(self));
+                    },
+                }
+                match self.getc()? {
+                    Some(b'u') => {},
+                    next => {
+                        self.unget_lossy(next);
+                        self.unget_lossy(Some(b'\\'));
+                        return Err(unpaired(self));
+                    },
+                }
+                let l = self.read_hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&l) {
+                    return Err(invalid(
+                        format!("expected a low surrogate after '\\u{:04X}' but got '\\u{:04X}'", h, l),
+                        self.pos(),
+                    ));
+                }
+                0x10000 + ((h - 0xD800) << 10 | (l - 0xDC00))
+            },
+            l @ 0xDC00..=0xDFFF => return Err(invalid(format!("unpaired low surrogate '\\u{:04X}'", l), self.pos())),
+            u => u,
+        };
+
+        char::from_u32(u).ok_or_else(|| invalid(format!("invalid code point U+{:04X}", u), self.pos()))
+    }
+
+    /// Puts back a byte that ended an invalid escape early, so that with
+    /// `lossy_strings` it is read again as part of the string.
+    fn unget_lossy(&mut self, c: Option<u8>) {
+        if let Some(c) = c.filter(|_| self.options.lossy_strings) {
+            self.ungetc(c);
+        }
+    }
+
+    fn read_hex4(&mut self) -> Result<u32, JsonError> {
+        let mut u: u32 = 0;
+
+        for _ in 0..4 {
+            let d = match self.getc()? {
+                Some(c) => match (c as char).to_digit(16) {
+                    Some(d) => d,
+                    None => {
+                        let err = JsonError::InvalidEscape {
+                            msg: format!("invalid hex digit '{}' in '\\u' escape", c as char),
+                            pos: self.pos(),
+                        };
+                        self.unget_lossy(Some(c));
+                        return Err(err);
+                    },
+                },
+                None => return Err(JsonError::UnexpectedEof { msg: "unexpected end in '\\u' escape".into(), pos: self.pos() }),
+            };
+            u = u << 4 | d;
+        }
+
+        Ok(u)
+    }
+
+    fn read_literal(&mut self, s: &[u8]) -> Result<(), JsonError> {
+        for &e in s {
+            let b = self.getc()?;
+            
+            if let Some(x) = b {
+                if x != e {
+                    return Err(JsonError::UnexpectedChar {
+                        msg: format!("expected '{}' but found {}", e as char, show(x)),
+                        pos: self.pos(),
+                    });
+                }
+            } else {
+                return Err(JsonError::UnexpectedEof {
+                    msg: format!("unexpected end instead of '{}'", e as char),
+                    pos: self.pos(),
+                });
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// Checks whether the value at the current path can have nothing to
+    /// report, being neither inside the subtree nor one of its parents.
+    fn outside_subtree(&self) -> bool {
+        self.options.subtree.as_ref().is_some_and(|s| {
+            !path::is_within(s, &self.path, self.options.separator) && !path::is_within(&self.path, s, self.options.separator)
+        })
+    }
+
+    /// Handles the next token `c` inside the innermost container, an array.
+    fn array_token(&mut self, state: State, c: Option<u8>) -> Result<Option<JsonType>, JsonError> {
+        let Some(c) = c else {
+            return Err(JsonError::UnexpectedEof { msg: "unexpected end of array".into(), pos: self.pos() });
+        };
+
+        match (state, c) {
+            (State::AfterComma, b']') => {
+                Err(JsonError::UnexpectedChar { msg: "trailing comma before ']'".into(), pos: self.pos() })
+            },
+            (_, b']') => Ok(Some(self.close_container())),
+            (State::First | State::AfterValue, b'}') => {
+                Err(JsonError::UnexpectedChar { msg: "mismatched '}': expected ']'".into(), pos: self.pos() })
+            },
+            (State::AfterValue, b',') => {
+                let frame = self.stack.last_mut().expect("array frame");
+                frame.index += 1;
+                frame.state = State::AfterComma;
+                self.path.truncate(frame.path_len);
+                Ok(None)
+            },
+            (State::AfterValue, _) => Err(self.letter_after_number(c).unwrap_or_else(|| JsonError::UnexpectedChar {
+                msg: format!("expected ',' or ']' but found {} in array", show(c)),
+                pos: self.pos(),
+            })),
+            (_, b',') => {
+                Err(JsonError::UnexpectedChar { msg: "expected a value before ','".into(), pos: self.pos() })
+            },
+            _ => {
+                let frame = self.stack.last_mut().expect("array frame");
+                if let Some(max) = self.options.max_array_elements.filter(|&max| frame.index >= max) {
+                    return Err(JsonError::TooManyElements { max, pos: self.pos() });
+                }
+                frame.state = State::AfterValue;
+                let path_len = frame.path_len;
+                if !self.skipping {
+                    push_index(&mut self.path, frame.index);
+                    if self.outside_subtree() {
+                        self.path.truncate(path_len);
+                        self.skip_outside();
+                    }
+                }
+                self.begin_value(c, false)
+            },
+        }
+    }
+
+    /// Adds the key just read to the path and readies the object for the
+    /// `:` that follows.
+    fn end_key(&mut self) -> Result<(), JsonError> {
+        match (self.skipping, self.options.lowercase_keys) {
+            (true, _) => {},
+            (false, true) => push_key(&mut self.path, &self.key.to_lowercase(), self.options.separator),
+            (false, false) => push_key(&mut self.path, &self.key, self.options.separator),
+        }
+        let outside = !self.skipping && self.outside_subtree();
+        let frame = self.stack.last_mut().expect("object frame");
+        if let Some(on_key) = self.hooks.on_key.as_mut().filter(|_| !self.skipping && !outside) {
+            on_key(&self.path[..frame.path_len], &self.key);
+        }
+        if self.options.reject_duplicate_keys && !frame.seen.insert(self.key.clone()) {
+            return Err(JsonError::DuplicateKey { key: self.key.clone(), pos: self.pos() });
+        }
+        frame.state = State::Colon;
+        if outside {
+            self.path.truncate(frame.path_len);
+            self.skip_outside();
+        }
+        Ok(())
+    }
+
+    /// Handles the next token `c` inside the innermost container, an object.
+    fn object_token(&mut self, state: State, c: Option<u8>) -> Result<Option<JsonType>, JsonError> {
+        let Some(c) = c else {
+            return Err(JsonError::UnexpectedEof { msg: "unexpected end of object".into(), pos: self.pos() });
+        };
+
+        match (state, c) {
+            (State::AfterComma, b'}') => {
+                Err(JsonError::UnexpectedChar { msg: "trailing comma before '}'".into(), pos: self.pos() })
+            },
+            (State::First | State::AfterValue, b'}') => Ok(Some(self.close_container())),
+            (State::First | State::AfterValue, b']') => {
+                Err(JsonError::UnexpectedChar { msg: "mismatched ']': expected '}'".into(), pos: self.pos() })
+            },
+            (State::First | State::AfterComma, b'"' | b'\'') if c == b'"' || self.options.single_quotes => {
+                let index = self.stack.last().expect("object frame").index;
+                if let Some(max) = self.options.max_object_members.filter(|&max| index >= max) {
+                    return Err(JsonError::TooManyMembers { max, pos: self.pos() });
+                }
+                self.read_string(true, c)?;
+                self.end_key()?;
+                Ok(None)
+            },
+            (State::First | State::AfterComma, b',') => {
+                Err(JsonError::UnexpectedChar { msg: "expected a member before ','".into(), pos: self.pos() })
+            },
+            (State::First | State::AfterComma, b':') => {
+                Err(JsonError::UnexpectedChar { msg: "expecting a key before ':'".into(), pos: self.pos() })
+            },
+            (State::First | State::AfterComma, b'\'') => Err(JsonError::UnexpectedChar {
+                msg: "strings must use double quotes, not single quotes".into(),
+                pos: self.pos(),
+            }),
+            (State::First | State::AfterComma, _) => Err(JsonError::UnexpectedChar {
+                msg: format!("object keys must be strings but found {}", show(c)),
+                pos: self.pos(),
+            }),
+            (State::Colon, b':') => {
+                self.stack.last_mut().expect("object frame").state = State::MemberValue;
+                Ok(None)
+            },
+            (State::Colon, b'"') => Err(JsonError::UnexpectedChar {
+                msg: format!("expecting a ':' after '{}'", self.path),
+                pos: self.pos(),
+            }),
+            (State::Colon, _) => Err(JsonError::UnexpectedChar {
+                msg: format!("expected ':' but found {} in object", show(c)),
+                pos: self.pos(),
+            }),
+            (State::MemberValue, b':') => Err(JsonError::UnexpectedChar {
+                msg: "expected a value after ':' but found another ':'".into(),
+                pos: self.pos(),
+            }),
+            (State::MemberValue, b',' | b'}') => Err(JsonError::UnexpectedChar {
+                msg: format!("expected a value after ':' but found {}", show(c)),
+                pos: self.pos(),
+            }),
+            (State::MemberValue, _) => {
+                self.stack.last_mut().expect("object frame").state = State::AfterValue;
+                self.begin_value(c, true)
+            },
+            (State::AfterValue, b',') => {
+                let frame = self.stack.last_mut().expect("object frame");
+                frame.index += 1;
+                frame.state = State::AfterComma;
+                self.path.truncate(frame.path_len);
+                Ok(None)
+            },
+            (State::AfterValue, _) => Err(self.letter_after_number(c).unwrap_or_else(|| JsonError::UnexpectedChar {
+                msg: format!("expected ',' or '}}' but found {} in object", show(c)),
+                pos: self.pos(),
+            })),
+        }
+    }
+}
+
+fn would_block(e: &JsonError) -> bool {
+    matches!(e, JsonError::Io { error, .. } if error.kind() == io::ErrorKind::WouldBlock)
+}
+
+/// Empties `segments` and returns it for segments borrowing from somewhere
+/// else. Collecting into a vector of the same layout reuses the allocation.
+fn recycle<'b>(mut segments: Vec<PathSegment<'_>>) -> Vec<PathSegment<'b>> {
+    segments.clear();
+    segments.into_iter().map(|_| unreachable!("the vector is empty")).collect()
+}
+
+/// Replaces each invalid UTF-8 sequence in `buf` with U+FFFD. Unless this
+/// is the `last` of the string, an incomplete sequence at the end is kept.
+fn replace_invalid_utf8(buf: &mut Vec<u8>, last: bool) {
+    let mut start = 0;
+    while let Err(e) = std::str::from_utf8(&buf[start..]) {
+        let at = start + e.valid_up_to();
+        let len = match e.error_len() {
+            Some(len) => len,
+            None if last => buf.len() - at,
+            None => return,
+        };
+        buf.splice(at..at + len, char::REPLACEMENT_CHARACTER.encode_utf8(&mut [0; 4]).bytes());
+        start = at + char::REPLACEMENT_CHARACTER.len_utf8();
+    }
+}
+
+/// Renders an input byte for error messages.
+fn show(c: u8) -> String {
+    if c.is_ascii_graphic() || c == b' ' {
+        format!("'{}'", c as char)
+    } else {
+        format!("byte 0x{:02x}", c)
+    }
+}
+
+/// Appends an array index to `path` as `[i]`. The digits are formatted on
+/// the stack, which is about twice as fast as `to_string()` or `write!`,
+/// see `benches/paths.rs`.
+fn push_index(path: &mut String, mut i: usize) {
+    let mut digits = [0; 20];
+    let mut n = digits.len();
+    loop {
+        n -= 1;
+        digits[n] = b'0' + (i % 10) as u8;
+        i /= 10;
+        if i == 0 {
+            break;
+        }
+    }
+    path.push('[');
+    path.push_str(std::str::from_utf8(&digits[n..]).expect("ASCII digits"));
+    path.push(']');
+}
+
+/// Appends an object key to `path`, after `separator` when the key is an
+/// identifier and in bracket notation (`['a.b']`) otherwise.
+fn push_key(path: &mut String, key: &str, separator: char) {
+    let mut chars = key.chars();
+    let is_ident = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+
+    if is_ident {
+        path.push(separator);
+        path.push_str(key);
+        return;
+    }
+
+    path.push_str("['");
+    for c in key.chars() {
+        if c == '\'' || c == '\\' {
+            path.push('\\');
+        }
+        path.push(c);
+    }
+    path.push_str("']");
+}
+
+fn check_number(s: &str) -> Result<(), String> {
+    let b = s.as_bytes();
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while b.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1;
+        }
+        *i - start
+    };
+    let invalid = |msg: &str| Err(format!("invalid number '{}': {}", s, msg));
+
+    let mut i = 0;
+    if b.first() == Some(&b'-') {
+        i += 1;
+    }
+
+    match b.get(i) {
+        Some(b'0') => {
+            i += 1;
+            if b.get(i).is_some_and(u8::is_ascii_digit) {
+                return invalid("leading zeros not allowed");
+            }
+        },
+        Some(b'1'..=b'9') => {
+            digits(&mut i);
+        },
+        _ => return invalid("expected a digit"),
+    }
+
+    if b.get(i) == Some(&b'.') {
+        i += 1;
+        if digits(&mut i) == 0 {
+            return invalid("expected a digit after '.'");
+        }
+    }
+
+    if let Some(b'e' | b'E') = b.get(i) {
+        i += 1;
+        if let Some(b'+' | b'-') = b.get(i) {
+            i += 1;
+        }
+        if digits(&mut i) == 0 {
+            return invalid("expected a digit in the exponent");
+        }
+    }
+
+    match b.get(i) {
+        Some(&c) => invalid(&format!("unexpected '{}'", c as char)),
+        None => Ok(()),
+    }
+}
+
+/// Parses the text of a `Number` value as reported to the callback.
+///
+/// The text is validated with the same rules the parser uses, so anything
+/// that was emitted as a number is accepted here, including `NaN`,
+/// `Infinity` and `-Infinity`.
+pub fn parse_number(s: &str) -> Result<f64, String> {
+    match s {
+        "NaN" => return Ok(f64::NAN),
+        "Infinity" => return Ok(f64::INFINITY),
+        "-Infinity" => return Ok(f64::NEG_INFINITY),
+        _ => {},
+    }
+    check_number(s)?;
+    s.parse().map_err(|e| format!("invalid number '{}': {}", s, e))
+}
+
+/// Parses the text of an `Integer` value into an `i64`, failing if it
+/// does not fit. The text itself keeps every digit, however long.
+pub fn parse_i64(s: &str) -> Result<i64, String> {
+    parse_integer(s, "i64")
+}
+
+/// Parses the text of an `Integer` value into a `u64`, failing if it is
+/// negative or does not fit.
+pub fn parse_u64(s: &str) -> Result<u64, String> {
+    parse_integer(s, "u64")
+}
+
+fn parse_integer<I: std::str::FromStr>(s: &str, name: &str) -> Result<I, String> {
+    check_number(s)?;
+    s.parse().map_err(|_| if s.contains(['.', 'e', 'E']) {
+        format!("number '{}' is not an integer", s)
+    } else {
+        format!("integer '{}' out of range for {}", s, name)
+    })
+}
+
+pub fn json_parse<T, F>(input: T, mut on_value: F) -> Result<(), JsonError>
+where
+    T: std::io::Read, 
+    F: FnMut(&str, JsonType, &str)
+{
+    json_parse_until(input, |p, t, v| {
+        on_value(p, t, v);
+        ControlFlow::Continue(())
+    })
+}
+
+/// Parses a document held in a string, this is [`json_parse_slice`] over
+/// `s.as_bytes()`.
+pub fn json_parse_str<F>(s: &str, on_value: F) -> Result<(), JsonError>
+where
+    F: FnMut(&str, JsonType, &str)
+{
+    json_parse_slice(s.as_bytes(), on_value)
+}
+
+/// Parses a document held in memory, such as a `Vec<u8>` or a byte string
+/// literal, like [`json_parse`] but faster, see [`JsonParser::from_slice`].
+pub fn json_parse_slice<B, F>(data: &B, mut on_value: F) -> Result<(), JsonError>
+where
+    B: AsRef<[u8]> + ?Sized,
+    F: FnMut(&str, JsonType, &str)
+{
+    JsonParser::from_slice(data.as_ref(), |e: &Event| {
+        on_value(e.path, e.typ, e.value);
+        ControlFlow::Continue(())
+    }).parse()
+}
+
+/// Checks that `data` is a well-formed document, parsing it like
+/// [`json_parse_slice`] and ignoring the values. Also a simple target for
+/// fuzzers.
+pub fn parse_and_discard(data: &[u8]) -> Result<(), JsonError> {
+    JsonParser::from_slice(data, |_: &Event| ControlFlow::Continue(())).parse()
+}
+
+/// Like [`json_parse`], but stops as soon as the callback returns
+/// `ControlFlow::Break`, leaving the rest of the input unread.
+pub fn json_parse_until<T, F>(input: T, mut on_value: F) -> Result<(), JsonError>
+where
+    T: std::io::Read,
+    F: FnMut(&str, JsonType, &str) -> ControlFlow<()>
+{
+    JsonParser::new(input, |e: &Event| on_value(e.path, e.typ, e.value)).parse()
+}
+
+/// Parses only the first value of the input and returns the number of bytes
+/// up to and including it, leaving whatever follows unchecked. Bytes read
+/// ahead to find the end of a number are not counted, so the count can be
+/// used to find where the next value in the input starts.
+pub fn json_parse_first<T, F>(input: T, mut on_value: F) -> Result<usize, JsonError>
+where
+    T: std::io::Read,
+    F: FnMut(&str, JsonType, &str)
+{
+    let mut parser = JsonParserBuilder::new()
+        .strict_trailing(false)
+        .build(input, |e: &Event| {
+            on_value(e.path, e.typ, e.value);
+            ControlFlow::Continue(())
+        });
+    parser.parse()?;
+    Ok(parser.bytes_consumed())
+}
+
+/// Parses newline-delimited JSON (JSON lines), see [`JsonParser::parse_lines`].
+pub fn json_parse_lines<T, F>(input: T, mut on_value: F) -> Result<(), JsonError>
+where
+    T: std::io::Read,
+    F: FnMut(&str, JsonType, &str)
+{
+    JsonParser::new(input, |e: &Event| {
+        on_value(e.path, e.typ, e.value);
+        ControlFlow::Continue(())
+    }).parse_lines()
+}
+
+/// Parses whitespace separated values, see [`JsonParser::parse_stream`].
+pub fn json_parse_stream<T, F>(input: T, mut on_value: F) -> Result<(), JsonError>
+where
+    T: std::io::Read,
+    F: FnMut(&str, JsonType, &str)
+{
+    JsonParser::new(input, |e: &Event| {
+        on_value(e.path, e.typ, e.value);
+        ControlFlow::Continue(())
+    }).parse_stream()
+}
+
+type IgnoreEvent = fn(&Event) -> ControlFlow<()>;
+
+/// An iterator over the values of a document, created by [`json_events`].
+///
+/// Each call to `next` resumes the parser where the previous one stopped.
+/// The open containers, the current path and any input that has been read
+/// into the buffer but not parsed yet are kept in the parser between calls,
+/// so the reader is only read as far as the next event needs.
+///
+/// The iterator ends after the top-level value, or after the first error.
+pub struct JsonEvents<T: io::Read> {
+    parser: JsonParser<'static, T, IgnoreEvent>,
+    done: bool,
+}
+
+impl<T: io::Read> Iterator for JsonEvents<T> {
+    type Item = Result<(String, JsonType, String), JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.parser.next_event() {
+            Ok(Some(typ)) => Some(Ok((self.parser.path.clone(), typ, self.parser.current_value().to_string()))),
+            Ok(None) => {
+                self.done = true;
+                self.parser.finish().err().map(Err)
+            },
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+impl<T: io::Read> JsonEvents<T> {
+    /// Skips the next value without yielding it or the values inside of
+    /// it, see [`JsonParser::skip_value`].
+    pub fn skip_value(&mut self) -> Result<(), JsonError> {
+        self.parser.skip_value()
+    }
+}
+
+/// Returns the values of a document as an iterator of `(path, type, value)`
+/// instead of reporting them to a callback.
+pub fn json_events<T: io::Read>(input: T) -> JsonEvents<T> {
+    let ignore: IgnoreEvent = |_| ControlFlow::Continue(());
+    JsonEvents { parser: JsonParser::new(input, ignore), done: false }
+}
+
+/// Parses a document and returns all of its values as owned
+/// `(path, type, value)` tuples, in the order they are reported.
+pub fn collect_events<T: io::Read>(input: T) -> Result<Vec<(String, JsonType, String)>, JsonError> {
+    json_events(input).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number() {
+        let data = b"1234.56";
+        json_parse(data.as_slice(), |p, t, v| {
+            assert_eq!("$", p);
+            assert_eq!(JsonType::Number, t);
+            assert_eq!("1234.56", v);
+        }).expect("failed to parse");
+    }
+
+    fn events(data: &[u8]) -> Result<Vec<(String, JsonType, String)>, String> {
+        collect_events(data).map_err(|e| e.to_string())
+    }
+
+    fn parse_string(data: &[u8]) -> Result<String, String> {
+        let mut out = None;
+        json_parse(data, |_, t, v| {
+            assert_eq!(JsonType::String, t);
+            out = Some(v.to_string());
+        }).map_err(|e| e.to_string())?;
+        Ok(out.expect("no value emitted"))
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!("\"", parse_string(br#""\"""#).unwrap());
+        assert_eq!("\\", parse_string(br#""\\""#).unwrap());
+        assert_eq!("/", parse_string(br#""\/""#).unwrap());
+        assert_eq!("\u{8}", parse_string(br#""\b""#).unwrap());
+        assert_eq!("\u{c}", parse_string(br#""\f""#).unwrap());
+        assert_eq!("\n", parse_string(br#""\n""#).unwrap());
+        assert_eq!("\r", parse_string(br#""\r""#).unwrap());
+        assert_eq!("\t", parse_string(br#""\t""#).unwrap());
+    }
+
+    #[test]
+    fn test_string_slash() {
+        assert_eq!("a/b", parse_string(br#""a\/b""#).unwrap());
+        assert_eq!("a/b", parse_string(br#""a/b""#).unwrap());
+        assert_eq!("//", parse_string(br#""\//""#).unwrap());
+
+        let out = events_with_comments(br#"{"a\/b": "/*x*/"} // c"#).unwrap();
+        assert_eq!(("$['a/b']".to_string(), JsonType::String, "/*x*/".to_string()), out[0]);
+    }
+
+    #[test]
+    fn test_string_mixed_escapes() {
+        assert_eq!("a\nb\t\"c\"\\d/", parse_string(br#""a\nb\t\"c\"\\d\/""#).unwrap());
+    }
+
+    #[test]
+    fn test_string_invalid_escape() {
+        let err = parse_string(br#""a\xb""#).unwrap_err();
+        assert!(err.contains("invalid escape"), "{}", err);
+    }
+
+    #[test]
+    fn test_nul_escape() {
+        let err = parse_string(br#""a\0b""#).unwrap_err();
+        assert_eq!("invalid escape '\\0' at $, line 1 column 4", err);
+
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+            .allow_nul_escape(true)
+            .build(br#"["a\0b", "\00"]"#.as_slice(), |e: &Event| {
+                out.push(e.value.to_string());
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        assert_eq!(vec!["a\0b", "\u{0}0", ""], out);
+    }
+
+    #[test]
+    fn test_string_unicode_escape() {
+        assert_eq!("\u{e9}", parse_string(br#""\u00e9""#).unwrap());
+        assert_eq!("caf\u{e9}!", parse_string(br#""caf\u00E9!""#).unwrap());
+        assert_eq!("\u{20ac}", parse_string(br#""\u20ac""#).unwrap());
+        assert_eq!("\0", parse_string(br#""\u0000""#).unwrap());
+    }
+
+    #[test]
+    fn test_string_unicode_escape_invalid() {
+        let err = parse_string(br#""\u00g9""#).unwrap_err();
+        assert!(err.contains("invalid hex digit 'g'"), "{}", err);
+
+        let err = parse_string(br#""\u00"#).unwrap_err();
+        assert!(err.contains("unexpected end"), "{}", err);
+    }
+
+    #[test]
+    fn test_string_surrogate_pair() {
+        assert_eq!("\u{1f600}", parse_string(br#""\uD83D\uDE00""#).unwrap());
+        assert_eq!("a\u{1d11e}b", parse_string(br#""a\ud834\udd1eb""#).unwrap());
+    }
+
+    #[test]
+    fn test_string_surrogate_pair_invalid() {
+        let err = parse_string(br#""\uD83Dx""#).unwrap_err();
+        assert!(err.contains("unpaired high surrogate '\\uD83D'"), "{}", err);
+
+        let err = parse_string(br#""\uD83D\u0041""#).unwrap_err();
+        assert!(err.contains("'\\u0041'"), "{}", err);
+
+        let err = parse_string(br#""\uDE00\uD83D""#).unwrap_err();
+        assert!(err.contains("unpaired low surrogate '\\uDE00'"), "{}", err);
+    }
+
+    #[test]
+    fn test_string_lone_surrogates() {
+        assert_eq!(Err("unpaired high surrogate '\\uD834' at $, line 1 column 8".to_string()), parse_string(br#""\uD834""#));
+        assert_eq!(Err("unpaired low surrogate '\\uDD1E' at $, line 1 column 7".to_string()), parse_string(br#""\uDD1E""#));
+        assert_eq!(Err("unpaired low surrogate '\\uDFFF' at $, line 1 column 7".to_string()), parse_string(br#""\uDFFF""#));
+
+        let err = collect_events(br#"{"\udd1e": 1}"#.as_slice()).unwrap_err();
+        assert!(matches!(err, JsonError::InvalidEscape { .. }), "{:?}", err);
+        let err = collect_events(br#"["a", "\uD834"]"#.as_slice()).unwrap_err();
+        assert!(matches!(err, JsonError::InvalidEscape { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_lossy_strings() {
+        let lossy = |data: &[u8]| {
+            let mut out = Vec::new();
+            JsonParserBuilder::new()
+                .lossy_strings(true)
+                .build(data, |e: &Event| {
+                    out.push(format!("{}={}", e.path, e.value));
+                    ControlFlow::Continue(())
+                })
+                .parse()
+                .map(|_| out)
+        };
+
+        assert_eq!(vec!["$[0]=a\u{fffd}b", "$[1]=x", "$="], lossy(br#"["a\qb", "x"]"#).unwrap());
+        assert_eq!(vec!["$=caf\u{fffd} \u{fffd}\u{fffd}!"], lossy(b"\"caf\xc3 \xff\xfe!\"").unwrap());
+        assert_eq!(vec!["$=\u{fffd}"], lossy(b"\"\xe2\x82\"").unwrap());
+        assert_eq!(vec!["$=\u{fffd}"], lossy(br#""\uD834""#).unwrap());
+        assert_eq!(vec!["$=\u{fffd}x"], lossy(br#""\uDD1Ex""#).unwrap());
+        assert_eq!(vec!["$=\u{fffd}\n"], lossy(br#""\uD834\n""#).unwrap());
+        assert_eq!(vec!["$=\u{fffd}"], lossy(br#""\u12""#).unwrap());
+        assert_eq!(vec!["$['\u{fffd}']=1", "$="], lossy(br#"{"\x": 1}"#).unwrap());
+
+        let mut chunks = String::new();
+        JsonParserBuilder::new()
+            .lossy_strings(true)
+            .string_chunks(2, |_, chunk, _| chunks.push_str(chunk))
+            .build(b"\"ab\xffcd\\q\xe2\x82\"".as_slice(), |_: &Event| ControlFlow::Continue(()))
+            .parse()
+            .unwrap();
+        assert_eq!("ab\u{fffd}cd\u{fffd}\u{fffd}", chunks);
+
+        assert!(events(br#""a\qb""#).is_err());
+        assert!(lossy(br#""\u12"#).is_err());
+    }
+
+    #[test]
+    fn test_string_utf8() {
+        assert_eq!("caf\u{e9}", parse_string("\"caf\u{e9}\"".as_bytes()).unwrap());
+        assert_eq!("\u{1f600} \u{20ac}", parse_string("\"\u{1f600} \u{20ac}\"".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_string_invalid_utf8() {
+        let err = parse_string(b"\"caf\xc3\"").unwrap_err();
+        assert!(err.contains("invalid UTF-8"), "{}", err);
+
+        let err = parse_string(b"\"\xff\"").unwrap_err();
+        assert!(err.contains("invalid UTF-8"), "{}", err);
+    }
+
+    #[test]
+    fn test_string_control_characters() {
+        let err = parse_string(b"\"a\nb\"").unwrap_err();
+        assert!(err.contains("unescaped control character 0x0a in string"), "{}", err);
+
+        let err = parse_string(b"\"a\tb\"").unwrap_err();
+        assert!(err.contains("unescaped control character 0x09 in string"), "{}", err);
+
+        assert_eq!("a\nb", parse_string(br#""a\nb""#).unwrap());
+    }
+
+    #[test]
+    fn test_number_valid() {
+        for n in ["0.5", "-0.5e+10", "1E5", "1e-3", "2.50E+08", "0.0"] {
+            let ev = events(n.as_bytes()).unwrap();
+            assert_eq!(vec![("$".to_string(), JsonType::Number, n.to_string())], ev);
+        }
+        for n in ["0", "-0", "10", "-12"] {
+            let ev = events(n.as_bytes()).unwrap();
+            assert_eq!(vec![("$".to_string(), JsonType::Integer, n.to_string())], ev);
+        }
+    }
+
+    #[test]
+    fn test_number_invalid() {
+        for n in ["--1", "1.2.3", "1e", "-", "1.", "1.e5", "1e+", "1-2", "01"] {
+            let err = events(n.as_bytes()).unwrap_err();
+            assert!(err.starts_with(&format!("invalid number '{}'", n)), "{}: {}", n, err);
+        }
+
+        assert!(events(b"+5").is_err());
+        assert!(events(b".5").is_err());
+    }
+
+    #[test]
+    fn test_parse_number() {
+        let mut n = None;
+        json_parse(b"-1.5e2".as_slice(), |_, t, v| {
+            assert_eq!(JsonType::Number, t);
+            n = Some(parse_number(v).unwrap());
+        }).unwrap();
+        assert_eq!(Some(-150.0), n);
+
+        assert_eq!(Ok(0.0), parse_number("0"));
+        assert_eq!(Ok(1e300), parse_number("1e300"));
+        assert!(parse_number("1.").is_err());
+        assert!(parse_number(" 1").is_err());
+    }
+
+    #[test]
+    fn test_integer_vs_number() {
+        let typ = |s: &str| events(s.as_bytes()).unwrap().remove(0).1;
+        assert_eq!(JsonType::Integer, typ("42"));
+        assert_eq!(JsonType::Number, typ("42.0"));
+        assert_eq!(JsonType::Number, typ("1e3"));
+        assert_eq!(JsonType::Number, typ("1E+3"));
+    }
+
+    #[test]
+    fn test_big_integers() {
+        let big = "123456789012345678901234567890";
+        let (_, typ, value) = collect_events(big.as_bytes()).unwrap().remove(0);
+        assert_eq!((JsonType::Integer, big), (typ, value.as_str()));
+        assert_eq!(Err(format!("integer '{}' out of range for i64", big)), parse_i64(big));
+        assert_eq!(Err(format!("integer '-{}' out of range for u64", big)), parse_u64(&format!("-{}", big)));
+        assert_eq!(1.2345678901234568e29, parse_number(big).unwrap());
+
+        assert_eq!(Ok(i64::MAX), parse_i64("9223372036854775807"));
+        assert_eq!(Ok(i64::MIN), parse_i64("-9223372036854775808"));
+        assert!(parse_i64("9223372036854775808").unwrap_err().contains("out of range"));
+        assert!(parse_i64("-9223372036854775809").unwrap_err().contains("out of range"));
+        assert_eq!(Ok(9223372036854775808), parse_u64("9223372036854775808"));
+        assert_eq!(Ok(u64::MAX), parse_u64("18446744073709551615"));
+        assert!(parse_u64("18446744073709551616").unwrap_err().contains("out of range"));
+        assert!(parse_u64("-1").unwrap_err().contains("out of range"));
+
+        assert_eq!(Err("number '1.5' is not an integer".to_string()), parse_i64("1.5"));
+        assert_eq!(Err("number '1e3' is not an integer".to_string()), parse_u64("1e3"));
+        assert!(parse_i64("01").unwrap_err().contains("leading zeros"));
+    }
+
+    #[test]
+    fn test_error_position() {
+        let err = events(b"\n\n  \"a\\qb\"").unwrap_err();
+        assert!(err.ends_with("line 3 column 6"), "{}", err);
+
+        let err = events(b"1\n\n x").unwrap_err();
+        assert!(err.ends_with("line 3 column 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_error_kind() {
+        let err = json_parse(b"\"abc".as_slice(), |_, _, _| {}).unwrap_err();
+        assert!(matches!(err, JsonError::UnterminatedString { .. }), "{:?}", err);
+        assert_eq!(4, err.offset());
+
+        let err = json_parse(b"  1.x".as_slice(), |_, _, _| {}).unwrap_err();
+        assert!(matches!(err, JsonError::InvalidNumber { .. }), "{:?}", err);
+
+        let err = json_parse(br#""\q""#.as_slice(), |_, _, _| {}).unwrap_err();
+        assert!(matches!(err, JsonError::InvalidEscape { .. }), "{:?}", err);
+        assert_eq!(&Position { offset: 3, line: 1, column: 3, path: "$".into() }, err.position());
+
+        let err = json_parse(b"tru".as_slice(), |_, _, _| {}).unwrap_err();
+        assert!(matches!(err, JsonError::UnexpectedEof { .. }), "{:?}", err);
+        assert_eq!("unexpected end instead of 'e' at $, line 1 column 3", err.to_string());
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        for data in ["1 2", "true false", "{} garbage", "\"a\"\"b\"", "[]]"] {
+            let err = json_parse(data.as_bytes(), |_, _, _| {}).unwrap_err();
+            assert!(matches!(err, JsonError::TrailingData { .. }), "{}: {:?}", data, err);
+        }
+
+        assert!(events(b" 1 \n\t").is_ok());
+        assert!(events(b"[1, 2]").is_ok());
+        assert!(events(br#"{"a": 1, "b": "x"}"#).is_ok());
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let data = b"{\"a\":1}\n[true]\r\n\n\"x\"\n";
+        let mut out = Vec::new();
+        json_parse_lines(data.as_slice(), |p, t, v| out.push((p.to_string(), t, v.to_string()))).unwrap();
+        assert_eq!(vec![
+            ("$.a".to_string(), JsonType::Integer, "1".to_string()),
+            ("$".to_string(), JsonType::Object, "".to_string()),
+            ("$[0]".to_string(), JsonType::True, "".to_string()),
+            ("$".to_string(), JsonType::Array, "".to_string()),
+            ("$".to_string(), JsonType::String, "x".to_string()),
+        ], out);
+
+        assert!(json_parse_lines(b"1\n2".as_slice(), |_, _, _| {}).is_ok());
+        assert!(json_parse_lines(b"".as_slice(), |_, _, _| {}).is_ok());
+    }
+
+    #[test]
+    fn test_parse_lines_error() {
+        let err = json_parse_lines(b"1\n2\n[x]\n".as_slice(), |_, _, _| {}).unwrap_err();
+        assert!(matches!(err, JsonError::Record { record: 3, .. }), "{:?}", err);
+        assert_eq!(3, err.position().line);
+
+        let err = json_parse_lines(b"1 2\n".as_slice(), |_, _, _| {}).unwrap_err();
+        assert!(matches!(err, JsonError::Record { record: 1, .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        let stream = |data: &[u8]| {
+            let mut out = Vec::new();
+            json_parse_stream(data, |p, _, v| out.push(format!("{}={}", p, v))).map(|_| out)
+        };
+        assert_eq!(vec!["$=1", "$=2", "$=3"], stream(b"1 2 3").unwrap());
+        assert_eq!(vec!["$=1", "$=2", "$=3"], stream(b"\n\t 1\r\n\n   2\t\t3  \n\n").unwrap());
+        assert_eq!(vec!["$[0]=1", "$=", "$.a=x", "$=", "$=y", "$="], stream(br#"[1]{"a":"x"}"y" true"#).unwrap());
+        assert!(stream(b"").unwrap().is_empty());
+        assert!(stream(b"  \n ").unwrap().is_empty());
+
+        let err = stream(b"1 2 [3,]").unwrap_err();
+        assert_eq!("trailing comma before ']' at $, line 1 column 8", err.to_string());
+        assert!(matches!(stream(b"1 {"), Err(JsonError::UnexpectedEof { .. })));
+    }
+
+    struct Trickle<'a>(&'a [u8]);
+
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_large_input() {
+        let mut data = String::from("[");
+        for i in 0..400_000 {
+            if i > 0 {
+                data.push(',');
+            }
+            data.push_str(&i.to_string());
+        }
+        data.push(']');
+        assert!(data.len() > 2_000_000);
+
+        let mut count = 0;
+        let mut last = String::new();
+        json_parse(data.as_bytes(), |p, t, v| {
+            count += 1;
+            if t == JsonType::Integer {
+                last = format!("{}={}", p, v);
+            }
+        }).unwrap();
+        assert_eq!(400_001, count);
+        assert_eq!("$[399999]=399999", last);
+    }
+
+    #[test]
+    fn test_short_reads() {
+        let data = br#"{"key": "a longer \u00e9 string", "n": [1.5, -20]}"#;
+        let mut out = Vec::new();
+        json_parse(Trickle(data), |p, t, v| out.push((p.to_string(), t, v.to_string()))).unwrap();
+        assert_eq!(events(data).unwrap(), out);
+        assert_eq!(("$.key".to_string(), JsonType::String, "a longer \u{e9} string".to_string()), out[0]);
+    }
+
+    #[test]
+    fn test_container_events() {
+        let data = br#"{"a": [1, {}], "b": null}"#;
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+        .container_events(true)
+        .build(data.as_slice(), |e: &Event| {
+            out.push((e.path.to_string(), e.typ));
+            ControlFlow::Continue(())
+        })
+        .parse()
+        .unwrap();
+
+        let expected = [
+            ("$", JsonType::ObjectStart),
+            ("$.a", JsonType::ArrayStart),
+            ("$.a[0]", JsonType::Integer),
+            ("$.a[1]", JsonType::ObjectStart),
+            ("$.a[1]", JsonType::ObjectEnd),
+            ("$.a", JsonType::ArrayEnd),
+            ("$.b", JsonType::Null),
+            ("$", JsonType::ObjectEnd),
+        ];
+        assert_eq!(expected.map(|(p, t)| (p.to_string(), t)).to_vec(), out);
+    }
+
+    #[test]
+    fn test_parse_until() {
+        let data = br#"{"a": 1, "target": "found", "rest": [1, 2, 3], "broken": }"#;
+        let mut seen = Vec::new();
+        let mut target = None;
+        json_parse_until(data.as_slice(), |p, _, v| {
+            seen.push(p.to_string());
+            if p == "$.target" {
+                target = Some(v.to_string());
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        }).unwrap();
+
+        assert_eq!(Some("found".to_string()), target);
+        assert_eq!(vec!["$.a", "$.target"], seen);
+    }
+
+    fn paths(data: &[u8]) -> Vec<String> {
+        events(data).unwrap().into_iter().map(|(p, _, _)| p).collect()
+    }
+
+    #[test]
+    fn test_key_paths() {
+        assert_eq!(vec!["$.simple_key1", "$._x", "$"], paths(br#"{"simple_key1": 1, "_x": 2}"#));
+        assert_eq!(vec!["$['a.b']", "$"], paths(br#"{"a.b": 1}"#));
+        assert_eq!(vec!["$['with spaces']", "$"], paths(br#"{"with spaces": 1}"#));
+        assert_eq!(vec!["$['']", "$"], paths(br#"{"": 1}"#));
+        assert_eq!(vec!["$['1a']", "$"], paths(br#"{"1a": 1}"#));
+        assert_eq!(vec!["$['it\\'s']", "$"], paths(br#"{"it's": 1}"#));
+        assert_eq!(vec!["$['a\\\\b']", "$"], paths(br#"{"a\\b": 1}"#));
+        assert_eq!(vec!["$['a b'].c", "$['a b']", "$"], paths(br#"{"a b": {"c": 1}}"#));
+    }
+
+    #[test]
+    fn test_decoded_key_paths() {
+        assert_eq!(vec!["$.\u{e9}", "$"], paths(br#"{"\u00e9": 1}"#));
+        assert_eq!(vec!["$.caf\u{e9}", "$"], paths("{\"caf\u{e9}\": 1}".as_bytes()));
+        assert_eq!(vec!["$['a\nb']", "$"], paths(br#"{"a\nb": 1}"#));
+        assert_eq!(vec!["$['\u{1F600}']", "$"], paths(br#"{"\ud83d\ude00": 1}"#));
+        assert_eq!(vec!["$['\u{1F600}'].x", "$['\u{1F600}']", "$"], paths("{\"\u{1F600}\": {\"x\": 1}}".as_bytes()));
+        assert_eq!(vec!["$.ab", "$"], paths(br#"{"a\u0062": 1}"#));
+    }
+
+    #[test]
+    fn test_bracket_key_paths() {
+        assert_eq!(vec!["$['a[0]']", "$"], paths(br#"{"a[0]": 1}"#));
+        assert_eq!(vec!["$['a[']", "$"], paths(br#"{"a[": 1}"#));
+        assert_eq!(vec!["$['a]']", "$"], paths(br#"{"a]": 1}"#));
+        assert_eq!(vec!["$['a.b']", "$"], paths(br#"{"a.b": 1}"#));
+        assert_eq!(vec![r"$['\'']", "$"], paths(br#"{"'": 1}"#));
+        assert_eq!(vec!["$['a\"b']", "$"], paths(br#"{"a\"b": 1}"#));
+        assert_eq!(vec![r"$['\']']", "$"], paths(br#"{"']": 1}"#));
+        assert_eq!(vec!["$.a[0]", "$.a", "$['a[0]']", "$"], paths(br#"{"a": [1], "a[0]": 2}"#));
+    }
+
+    #[test]
+    fn test_value_offsets() {
+        let data = r#" {"a": [1, "x"], "b" :  true}"#;
+        let mut out = Vec::new();
+        JsonParser::new(data.as_bytes(), |e: &Event| {
+            out.push((e.path.to_string(), e.offset));
+            ControlFlow::Continue(())
+        }).parse().unwrap();
+
+        assert_eq!(vec![
+            ("$.a[0]".to_string(), data.find('1').unwrap()),
+            ("$.a[1]".to_string(), data.find("\"x").unwrap()),
+            ("$.a".to_string(), data.find('[').unwrap()),
+            ("$.b".to_string(), data.find("true").unwrap()),
+            ("$".to_string(), 1),
+        ], out);
+    }
+
+    fn nested(depth: usize) -> String {
+        "[".repeat(depth) + &"]".repeat(depth)
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let parse = |data: &str, max: Option<usize>| {
+            let mut b = JsonParserBuilder::new();
+            if let Some(max) = max {
+                b = b.max_depth(max);
+            }
+            b.build(data.as_bytes(), |_: &Event| ControlFlow::Continue(())).parse()
+        };
+
+        assert!(parse(&nested(128), None).is_ok());
+        let err = parse(&nested(129), None).unwrap_err();
+        assert!(matches!(err, JsonError::DepthExceeded { max: 128, .. }), "{:?}", err);
+        assert!(err.to_string().starts_with("maximum nesting depth exceeded"), "{}", err);
+
+        assert!(parse(r#"{"a": [{"b": []}]}"#, Some(4)).is_ok());
+        assert!(parse(r#"{"a": [{"b": [{}]}]}"#, Some(4)).is_err());
+        assert!(parse("1", Some(0)).is_ok());
+        assert!(parse("[]", Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        const DEPTH: usize = 100_000;
+        let data = r#"{"a": ["#.repeat(DEPTH) + "1" + &"]}".repeat(DEPTH);
+
+        // A small stack shows that the depth does not turn into recursion.
+        let stats = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(move || {
+                let mut count = 0;
+                let mut parser = JsonParserBuilder::new()
+                    .max_depth(2 * DEPTH)
+                    .build(data.as_bytes(), |_: &Event| {
+                        count += 1;
+                        ControlFlow::Continue(())
+                    });
+                parser.parse().unwrap();
+                let stats = parser.stats();
+                drop(parser);
+                assert_eq!(2 * DEPTH + 1, count);
+                stats
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(2 * DEPTH, stats.max_depth);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let parse = |data: &str| {
+            JsonParserBuilder::new()
+                .reject_duplicate_keys(true)
+                .build(data.as_bytes(), |_: &Event| ControlFlow::Continue(()))
+                .parse()
+        };
+
+        assert!(events(br#"{"a":1,"a":2}"#).is_ok());
+
+        let err = parse(r#"{"a":1,"a":2}"#).unwrap_err();
+        assert!(matches!(err, JsonError::DuplicateKey { ref key, .. } if key == "a"), "{:?}", err);
+
+        let err = parse(r#"{"\u0061":1,"a":2}"#).unwrap_err();
+        assert!(matches!(err, JsonError::DuplicateKey { .. }), "{:?}", err);
+
+        assert!(parse(r#"{"a":{"a":1},"b":{"a":2}}"#).is_ok());
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let err = events(b"[1,2,]").unwrap_err();
+        assert!(err.starts_with("trailing comma before ']'"), "{}", err);
+
+        let err = events(br#"{"a":1,}"#).unwrap_err();
+        assert!(err.starts_with("trailing comma before '}'"), "{}", err);
+
+        assert!(events(b"[,1]").is_err());
+        assert!(events(b"[1,,2]").is_err());
+        assert!(events(b"[,]").is_err());
+        assert!(events(b"{,}").is_err());
+        assert!(events(br#"{,"a":1}"#).is_err());
+        assert!(events(br#"{"a":1,,"b":2}"#).is_err());
+
+        assert!(events(b"[]").is_ok());
+        assert!(events(b"{}").is_ok());
+        assert!(events(br#"[1, 2, {"a": 1, "b": []}]"#).is_ok());
+    }
+
+    #[test]
+    fn test_event_keys() {
+        let data = br#"{"a": [1, {"b": 2}], "c": {"d": 3}}"#;
+        let mut out = Vec::new();
+        JsonParser::new(data.as_slice(), |e: &Event| {
+            out.push((e.typ, e.key.map(str::to_string)));
+            ControlFlow::Continue(())
+        }).parse().unwrap();
+
+        let key = |k: &str| Some(k.to_string());
+        assert_eq!(vec![
+            (JsonType::Integer, None),
+            (JsonType::Integer, key("b")),
+            (JsonType::Object, None),
+            (JsonType::Array, key("a")),
+            (JsonType::Integer, key("d")),
+            (JsonType::Object, key("c")),
+            (JsonType::Object, None),
+        ], out);
+    }
+
+    #[test]
+    fn test_event_parent() {
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+            .container_events(true)
+            .build(br#"{"a": [1, {"b": null}], "c": 2}"#.as_slice(), |e: &Event| {
+                out.push((e.typ, e.parent));
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        assert_eq!(vec![
+            (JsonType::ObjectStart, Parent::Root),
+            (JsonType::ArrayStart, Parent::ObjectMember),
+            (JsonType::Integer, Parent::ArrayElement),
+            (JsonType::ObjectStart, Parent::ArrayElement),
+            (JsonType::Null, Parent::ObjectMember),
+            (JsonType::ObjectEnd, Parent::ArrayElement),
+            (JsonType::ArrayEnd, Parent::ObjectMember),
+            (JsonType::Integer, Parent::ObjectMember),
+            (JsonType::ObjectEnd, Parent::Root),
+        ], out);
+    }
+
+    #[test]
+    fn test_path_segments() {
+        use PathSegment::{Index, Key};
+        let show = |s: &[PathSegment]| format!("{:?}", s);
+
+        let data = br#"{"a": [0, 1, {"b": true, "C": {}}]}"#;
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+            .path_segments(true)
+            .lowercase_keys(true)
+            .container_events(true)
+            .build(data.as_slice(), |e: &Event| {
+                if e.path.starts_with("$.a[2]") {
+                    out.push((e.path.to_string(), show(e.segments)));
+                }
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        assert_eq!(vec![
+            ("$.a[2]".to_string(), show(&[Key("a"), Index(2)])),
+            ("$.a[2].b".to_string(), show(&[Key("a"), Index(2), Key("b")])),
+            ("$.a[2].c".to_string(), show(&[Key("a"), Index(2), Key("C")])),
+            ("$.a[2].c".to_string(), show(&[Key("a"), Index(2), Key("C")])),
+            ("$.a[2]".to_string(), show(&[Key("a"), Index(2)])),
+        ], out);
+
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+            .path_segments(true)
+            .build(b"[[1]]".as_slice(), |e: &Event| {
+                out.push(show(e.segments));
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        assert_eq!(vec![show(&[Index(0), Index(0)]), show(&[Index(0)]), show(&[])], out);
+
+        let mut lens = Vec::new();
+        JsonParser::new(b"[[1]]".as_slice(), |e: &Event| {
+            lens.push(e.segments.len());
+            ControlFlow::Continue(())
+        }).parse().unwrap();
+        assert_eq!(vec![0, 0, 0], lens);
+    }
+
+    #[test]
+    fn test_event_index() {
+        let index = |data: &[u8], container_events| {
+            let mut out = Vec::new();
+            JsonParserBuilder::new()
+                .container_events(container_events)
+                .build(data, |e: &Event| {
+                    out.push(e.index);
+                    ControlFlow::Continue(())
+                })
+                .parse()
+                .unwrap();
+            out
+        };
+        assert_eq!(vec![Some(0), Some(1), Some(2), Some(3), None], index(b"[10, 20, 30, 40]", false));
+        assert_eq!(vec![Some(0), None, Some(0), None, Some(1), None], index(br#"[1, {"a": 2, "b": [3]}]"#, false));
+        assert_eq!(vec![None, Some(0), Some(1), Some(0), Some(1), None], index(b"[1, [2]]", true));
+    }
+
+    #[test]
+    fn test_bom() {
+        assert_eq!(events(b"[1]").unwrap(), events(b"\xEF\xBB\xBF[1]").unwrap());
+        assert_eq!(events(b"\"x\"").unwrap(), events(b"\xEF\xBB\xBF \"x\"").unwrap());
+
+        let err = events(b"\xEF\xBB\xBF\xEF\xBB\xBF1").unwrap_err();
+        assert!(err.ends_with("line 1 column 1"), "{}", err);
+        assert!(events(b"[\xEF\xBB\xBF1]").is_err());
+        assert!(events(b"1\xEF\xBB\xBF").is_err());
+        assert!(events(b"\xEF\xBB1").is_err());
+
+        let mut out = Vec::new();
+        json_parse_lines(b"\xEF\xBB\xBF1\n2".as_slice(), |_, _, v| out.push(v.to_string())).unwrap();
+        assert_eq!(vec!["1", "2"], out);
+    }
+
+    #[test]
+    fn test_parse_str_and_slice() {
+        let mut out = Vec::new();
+        json_parse_str(r#"{"a": "\u00e9"}"#, |p, _, v| out.push(format!("{}={}", p, v))).unwrap();
+        json_parse_slice(b"[true]", |p, t, _| out.push(format!("{}={:?}", p, t))).unwrap();
+        json_parse_slice(&vec![b'1'], |p, _, v| out.push(format!("{}={}", p, v))).unwrap();
+        assert_eq!(vec!["$.a=\u{e9}", "$=", "$[0]=True", "$=Array", "$=1"], out);
+
+        assert!(json_parse_str("[1,", |_, _, _| {}).is_err());
+    }
+
+    fn events_with_comments(data: &[u8]) -> Result<Vec<(String, JsonType, String)>, JsonError> {
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+        .allow_comments(true)
+        .build(data, |e: &Event| {
+            out.push((e.path.to_string(), e.typ, e.value.to_string()));
+            ControlFlow::Continue(())
+        })
+        .parse()?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_comments() {
+        let data = br#"// leading
+        {
+            "a": 1, // trailing
+            /* before key */ "b" /* after key */ : /* before value */ [2 /* in array */, 3]
+        } /* done */
+        // end"#;
+        let plain = br#"{"a": 1, "b": [2, 3]}"#;
+        assert_eq!(events(plain).unwrap(), events_with_comments(data).unwrap());
+
+        assert!(events(data).is_err());
+        assert!(events(b"1 // no").is_err());
+    }
+
+    #[test]
+    fn test_nested_block_comments() {
+        let data = b"/* outer /* inner */ still a comment */ 1";
+        assert_eq!(events(b"1").unwrap(), events_with_comments(data).unwrap());
+        assert_eq!(events(b"[1]").unwrap(), events_with_comments(b"[/** / * **/1]").unwrap());
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let err = events_with_comments(b"1 /* never closed").unwrap_err();
+        assert!(matches!(err, JsonError::UnexpectedEof { .. }), "{:?}", err);
+        assert!(err.to_string().starts_with("unterminated block comment"), "{}", err);
+
+        let err = events_with_comments(b"1 /* /* */").unwrap_err();
+        assert!(matches!(err, JsonError::UnexpectedEof { .. }), "{:?}", err);
+
+        assert!(events_with_comments(b"1 / 2").is_err());
+        assert!(events_with_comments(b"1 // fine").is_ok());
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let msg = |data: &[u8]| events(data).unwrap_err();
+        assert!(msg(b"[1 x]").starts_with("expected ',' or ']' but found 'x' in array"), "{}", msg(b"[1 x]"));
+        assert!(msg(b"[1 2]").starts_with("expected ',' or ']' but found '2' in array"));
+        assert!(msg(b"x").starts_with("expected a value but found 'x'"));
+        assert!(msg(b"[\x01]").starts_with("expected a value but found byte 0x01"));
+        assert!(msg(b"{1}").starts_with("object keys must be strings but found '1'"));
+        assert!(msg(br#"{"a" x}"#).starts_with("expected ':' but found 'x' in object"));
+        assert!(msg(br#"{"a":1 x}"#).starts_with("expected ',' or '}' but found 'x' in object"));
+        assert!(msg(b"nul!").starts_with("expected 'l' but found '!'"));
+    }
+
+    #[test]
+    fn test_on_key() {
+        let data = br#"{"a": 1, "b": {"c": [{"d": null}], "e\u00e9": {}}}"#;
+        let mut keys = Vec::new();
+        let mut values = 0;
+        JsonParserBuilder::new()
+        .on_key(|p, k| keys.push(format!("{} {}", p, k)))
+        .build(data.as_slice(), |_: &Event| {
+            values += 1;
+            ControlFlow::Continue(())
+        })
+        .parse()
+        .unwrap();
+
+        assert_eq!(vec!["$ a", "$ b", "$.b c", "$.b.c[0] d", "$.b e\u{e9}"], keys);
+        assert_eq!(7, values);
+    }
+
+    #[test]
+    fn test_events_iterator() {
+        let data = br#"{"a": [1, "x", {"b": null}], "c": 2.5}"#;
+        let out: Vec<_> = json_events(data.as_slice()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(events(data).unwrap(), out);
+
+        let mut it = json_events(b"[1, x]".as_slice());
+        assert_eq!(("$[0]".to_string(), JsonType::Integer, "1".to_string()), it.next().unwrap().unwrap());
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+
+        let mut it = json_events(b"1 2".as_slice());
+        assert!(it.next().unwrap().is_ok());
+        assert!(matches!(it.next(), Some(Err(JsonError::TrailingData { .. }))));
+        assert!(it.next().is_none());
+
+        let mut it = json_events(b"".as_slice());
+        assert!(matches!(it.next(), Some(Err(JsonError::UnexpectedEof { .. }))));
+        assert!(it.next().is_none());
+    }
+
+    struct Broken;
+
+    impl std::io::Read for Broken {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("broken"))
+        }
+    }
+
+    #[test]
+    fn test_whitespace_between_tokens() {
+        let tokens = ["{", r#""a""#, ":", "[", "1", ",", "-2.5e3", ",", "true", ",", "{", "}", "]", ",", r#""b""#, ":", "null", "}"];
+        let expected = events(tokens.concat().as_bytes()).unwrap();
+        for ws in [" ", "\t", "\r\n", "\n\n  ", " /* c */ ", "// line\n"] {
+            let data = format!("{ws}{}{ws}", tokens.join(ws));
+            assert_eq!(expected, events_with_comments(data.as_bytes()).unwrap(), "{:?}", ws);
+        }
+
+        let mut out = Vec::new();
+        json_parse_stream(b" \t1\r\n[ 2 ]\n".as_slice(), |p, _, v| out.push(format!("{}={}", p, v))).unwrap();
+        assert_eq!(vec!["$=1", "$[0]=2", "$="], out);
+        assert!(json_parse_lines(b" \t1 \r\n\n [ 2 ] \n".as_slice(), |_, _, _| {}).is_ok());
+    }
+
+    #[test]
+    fn test_lenient_whitespace() {
+        let data = b"\x0c{\"a\":\x0b[1,\x0c2]\x0c}\x0b";
+        let err = events(data).unwrap_err();
+        assert!(err.starts_with("expected a value but found byte 0x0c"), "{}", err);
+        assert!(events(b"[1,\x0b2]").is_err());
+
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+            .lenient_whitespace(true)
+            .build(data.as_slice(), |e: &Event| {
+                out.push(format!("{}={}", e.path, e.value));
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        assert_eq!(vec!["$.a[0]=1", "$.a[1]=2", "$.a=", "$="], out);
+
+        let mut parser = JsonParserBuilder::new()
+            .lenient_whitespace(true)
+            .build(b"1\x0c\n2\n".as_slice(), |_: &Event| ControlFlow::Continue(()));
+        parser.parse_lines().unwrap();
+    }
+
+    #[test]
+    fn test_type_display() {
+        let names: Vec<String> = [
+            JsonType::String, JsonType::Number, JsonType::Integer, JsonType::True, JsonType::False, JsonType::Null,
+            JsonType::Array, JsonType::Object, JsonType::ArrayStart, JsonType::ArrayEnd, JsonType::ObjectStart,
+            JsonType::ObjectEnd,
+        ].iter().map(|t| t.to_string()).collect();
+        assert_eq!(vec![
+            "string", "number", "number", "true", "false", "null",
+            "array", "object", "array", "array", "object",
+            "object",
+        ], names);
+        assert_eq!("found a string at $", format!("found a {} at {}", JsonType::String, "$"));
+    }
+
+    #[test]
+    fn test_type_predicates() {
+        let all = [
+            JsonType::String, JsonType::Number, JsonType::Integer, JsonType::True, JsonType::False, JsonType::Null,
+            JsonType::Array, JsonType::Object, JsonType::ArrayStart, JsonType::ArrayEnd, JsonType::ObjectStart,
+            JsonType::ObjectEnd,
+        ];
+        let flags = |f: fn(JsonType) -> bool| all.iter().map(|&t| f(t) as u8).collect::<Vec<_>>();
+        assert_eq!(vec![1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0], flags(JsonType::is_scalar));
+        assert_eq!(vec![0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1], flags(JsonType::is_container));
+        assert_eq!(vec![0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0], flags(JsonType::is_bool));
+    }
+
+    #[test]
+    fn test_empty_string_values() {
+        assert_eq!(vec![
+            ("$.a".to_string(), JsonType::String, "".to_string()),
+            ("$.b".to_string(), JsonType::String, "x".to_string()),
+            ("$[''][0]".to_string(), JsonType::String, "".to_string()),
+            ("$['']".to_string(), JsonType::Array, "".to_string()),
+            ("$".to_string(), JsonType::Object, "".to_string()),
+        ], collect_events(br#"{"a": "", "b": "x", "": [""]}"#.as_slice()).unwrap());
+        assert_eq!(vec![("$".to_string(), JsonType::String, "".to_string())], events(br#""""#).unwrap());
+    }
+
+    #[test]
+    fn test_chained_readers() {
+        use std::io::Read;
+
+        let header = br#"{"id": 7, "name": "a long na"#.as_slice();
+        let body = br#"me", "esc": "\u00"#;
+        let tail = br#"e9", "n": 12"#;
+        let end = b"34}";
+        let input = header.chain(body.as_slice()).chain(tail.as_slice()).chain(end.as_slice());
+        assert_eq!(vec![
+            ("$.id".to_string(), JsonType::Integer, "7".to_string()),
+            ("$.name".to_string(), JsonType::String, "a long name".to_string()),
+            ("$.esc".to_string(), JsonType::String, "\u{e9}".to_string()),
+            ("$.n".to_string(), JsonType::Integer, "1234".to_string()),
+            ("$".to_string(), JsonType::Object, "".to_string()),
+        ], collect_events(input).unwrap());
+
+        let input = Trickle(b"[\"x").chain(Trickle(b"yz\"]"));
+        assert_eq!("xyz", collect_events(input).unwrap()[0].2);
+    }
+
+    #[test]
+    fn test_events_iterator_is_lazy() {
+        let input = std::io::Read::chain(b"[1, 2, ".as_slice(), Broken);
+        let mut it = json_events(input);
+        assert_eq!("1", it.next().unwrap().unwrap().2);
+        assert_eq!("2", it.next().unwrap().unwrap().2);
+        assert!(matches!(it.next(), Some(Err(JsonError::Io { .. }))));
+    }
+
+    struct TimedOut;
+
+    impl std::io::Read for TimedOut {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(io::ErrorKind::TimedOut, "too slow"))
+        }
+    }
+
+    #[test]
+    fn test_io_error() {
+        use std::error::Error;
+
+        let input = std::io::Read::chain(b"\n[1, ".as_slice(), TimedOut);
+        let err = json_parse(input, |_, _, _| {}).unwrap_err();
+        let JsonError::Io { error, pos } = &err else { panic!("{:?}", err) };
+        assert_eq!(io::ErrorKind::TimedOut, error.kind());
+        assert_eq!((5, 2), (pos.offset, pos.line));
+        assert_eq!("too slow at $, line 2 column 4", err.to_string());
+
+        let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(io::ErrorKind::TimedOut, source.kind());
+        assert!(JsonError::TrailingData { pos: Position::default() }.source().is_none());
+    }
+
+    #[test]
+    fn test_object_states() {
+        assert!(events(br#"{"a"}"#).is_err());
+        assert!(events(br#"{"a", "b": 1}"#).is_err());
+        assert!(events(br#"{"a": 1: 2}"#).is_err());
+        assert!(events(br#"{"a": }"#).is_err());
+        assert!(events(b"[1 2]").is_err());
+    }
+
+    #[test]
+    fn test_error_path() {
+        let data = br#"{"users": [{"age": 1}, {}, {"name": "x"}, {"name": "y", "age": 01}]}"#;
+        let err = collect_events(data.as_slice()).unwrap_err();
+        assert_eq!("$.users[3].age", err.position().path);
+        assert_eq!("invalid number '01': leading zeros not allowed at $.users[3].age, line 1 column 65", err.to_string());
+
+        assert_eq!("$.a['b c'][1]", collect_events(br#"{"a": {"b c": [1, "\x"]}}"#.as_slice()).unwrap_err().position().path);
+        assert_eq!("$.a[1]", collect_events(br#"{"a": [1, 2}"#.as_slice()).unwrap_err().position().path);
+        assert_eq!("$", collect_events(b"".as_slice()).unwrap_err().position().path);
+    }
+
+    fn recovering(data: &[u8]) -> (Vec<String>, Vec<String>) {
+        let mut out = Vec::new();
+        let errors = JsonParser::new(data, |e: &Event| {
+            out.push(format!("{}={}", e.path, e.value));
+            ControlFlow::Continue(())
+        }).parse_recovering();
+        (out, errors.iter().map(|e| e.to_string()).collect())
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let (out, errors) = recovering(br#"{"a": [1, 2x, 3], "b": tru, "c": "ok"}"#);
+        assert_eq!(vec!["$.a[0]=1", "$.a[1]=2", "$.a[2]=3", "$.a=", "$.c=ok", "$="], out);
+        assert_eq!(vec![
+            "unexpected 'x' after number at $.a[1], line 1 column 12",
+            "expected 'e' but found ',' at $.b, line 1 column 27",
+        ], errors);
+
+        let (out, errors) = recovering(b"[1 \"a,b\", 2]");
+        assert_eq!(vec!["$[0]=1", "$[1]=2", "$="], out);
+        assert_eq!(1, errors.len());
+
+        let (out, errors) = recovering(br#"["a\qb, c", {"x": {"y": [1}, "z": 3}, [01, 2], {"k" 1}, {,}]"#);
+        assert_eq!(vec![
+            "$[1].x.y[0]=1", "$[1].x=", "$[1].z=3", "$[1]=", "$[2][1]=2", "$[2]=", "$[3]=", "$[4]=", "$=",
+        ], out);
+        assert_eq!(vec![
+            "invalid escape '\\q' at $[0], line 1 column 5",
+            "mismatched '}': expected ']' at $[1].x.y[0], line 1 column 27",
+            "invalid number '01': leading zeros not allowed at $[2][0], line 1 column 41",
+            "expected ':' but found '1' in object at $[3].k, line 1 column 53",
+            "expected a member before ',' at $[4], line 1 column 58",
+            "trailing comma before '}' at $[4], line 1 column 59",
+        ], errors);
+
+        let mut out = Vec::new();
+        let mut parser = JsonParser::new(br#"["a\q"]"#.as_slice(), |e: &Event| {
+            out.push(e.path.to_string());
+            ControlFlow::Continue(())
+        });
+        assert!(parser.parse().is_err());
+        parser.reset(br#"[1 x, 2, "s"]"#.as_slice());
+        let errors = parser.parse_recovering();
+        assert_eq!(1, errors.len(), "{:?}", errors);
+        assert_eq!(vec!["$[0]", "$[1]", "$[2]", "$"], out);
+
+        let (out, errors) = recovering(b"[1, 2");
+        assert_eq!(vec!["$[0]=1", "$[1]=2"], out);
+        assert_eq!(vec!["unexpected end of array at $[1], line 1 column 5"], errors);
+
+        assert_eq!((vec![], vec!["expected a value but found 'x' at $, line 1 column 1".to_string()]), recovering(b"x"));
+        assert_eq!((vec!["$=1".to_string()], vec![]), recovering(b" 1 "));
+        assert_eq!(vec!["unexpected data after the top-level value at $, line 1 column 4"], recovering(b"[] {}").1);
+    }
+
+    #[test]
+    fn test_object_errors() {
+        let err = |data: &[u8]| events(data).unwrap_err();
+        assert_eq!("object keys must be strings but found '1' at $, line 1 column 2", err(b"{1:2}"));
+        assert_eq!("object keys must be strings but found 'a' at $, line 1 column 2", err(b"{a:1}"));
+        assert_eq!("object keys must be strings but found '2' at $, line 1 column 8", err(br#"{"a":1,2:3}"#));
+        assert_eq!("expected ':' but found '2' in object at $.a, line 1 column 6", err(br#"{"a" 2}"#));
+        assert_eq!("expected ':' but found '}' in object at $.a, line 1 column 5", err(br#"{"a"}"#));
+        assert_eq!("expected a value after ':' but found ',' at $.a, line 1 column 6", err(br#"{"a":,}"#));
+        assert_eq!("expected a value after ':' but found '}' at $.a, line 1 column 6", err(br#"{"a":}"#));
+        assert_eq!("expected ',' or '}' but found ':' in object at $.a, line 1 column 7", err(br#"{"a":1:2}"#));
+        assert_eq!("expected a member before ',' at $, line 1 column 2", err(b"{,}"));
+        assert_eq!("expecting a key before ':' at $, line 1 column 2", err(b"{:1}"));
+    }
+
+    #[test]
+    fn test_object_colons() {
+        let err = |data: &[u8]| events(data).unwrap_err();
+        assert_eq!("expected a value after ':' but found another ':' at $.a, line 1 column 6", err(br#"{"a"::1}"#));
+        assert_eq!("expected ',' or '}' but found ':' in object at $.a, line 1 column 7", err(br#"{"a":1::}"#));
+        assert_eq!("expecting a key before ':' at $, line 1 column 8", err(br#"{"a":1,:2}"#));
+        assert_eq!("expected ':' but found ',' in object at $.b, line 1 column 11", err(br#"{"a":1,"b","c":2}"#));
+        assert_eq!("expected ',' or ']' but found ':' in array at $[0], line 1 column 3", err(b"[1:2]"));
+        assert_eq!("unexpected data after the top-level value at $, line 1 column 4", err(br#""a":1"#));
+    }
+
+    #[test]
+    fn test_mismatched_brackets() {
+        let err = |data: &[u8]| events(data).unwrap_err();
+        assert_eq!("mismatched '}': expected ']' at $[1], line 1 column 5", err(b"[1,2}"));
+        assert_eq!("mismatched '}': expected ']' at $.a, line 1 column 8", err(br#"{"a": [}"#));
+        assert_eq!("mismatched ']': expected '}' at $.a, line 1 column 7", err(br#"{"a":1]"#));
+        assert_eq!("mismatched ']': expected '}' at $[0], line 1 column 3", err(b"[{]"));
+        assert_eq!("expected a value but found '}' at $[1], line 1 column 4", err(b"[1,}"));
+    }
+
+    #[test]
+    fn test_filter() {
+        let data = br#"{"users": [
+            {"name": "a", "email": "a@example.com", "tags": {"email": "no"}},
+            {"name": "b", "email": "b@example.com"}
+        ], "email": "top"}"#;
+        let filtered = |pattern: &str| {
+            let mut out = Vec::new();
+            JsonParserBuilder::new()
+            .filter(pattern)
+            .build(data.as_slice(), |e: &Event| {
+                out.push(format!("{}={}", e.path, e.value));
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+            out
+        };
+
+        assert_eq!(vec!["$.users[0].email=a@example.com", "$.users[1].email=b@example.com"], filtered("$.users[*].email"));
+        assert_eq!(vec!["$.users[0].tags.email=no"], filtered("$.users[*].*.email"));
+        assert_eq!(vec!["$.users[1].name=b"], filtered("$.users[1].name"));
+        assert_eq!(vec!["$.email=top"], filtered("$.email"));
+        assert!(filtered("$.nothing").is_empty());
+    }
+
+    #[test]
+    fn test_root_and_separator() {
+        let data = br#"{"a": {"b": [1]}, "c d": 2}"#;
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+        .root("#")
+        .separator('/')
+        .build(data.as_slice(), |e: &Event| {
+            out.push(e.path.to_string());
+            ControlFlow::Continue(())
+        })
+        .parse()
+        .unwrap();
+        assert_eq!(vec!["#/a/b[0]", "#/a/b", "#/a", "#['c d']", "#"], out);
+
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+        .root("")
+        .separator('/')
+        .filter("/*/b[*]")
+        .build(data.as_slice(), |e: &Event| {
+            out.push(e.path.to_string());
+            ControlFlow::Continue(())
+        })
+        .parse()
+        .unwrap();
+        assert_eq!(vec!["/a/b[0]"], out);
+    }
+
+    #[test]
+    fn test_initial_path() {
+        let data = br#"{"items": [{"id": 1}], "x": tru}"#;
+        let mut out = Vec::new();
+        let err = JsonParserBuilder::new()
+            .root("$.data")
+            .filter("$.data.items[*].id")
+            .build(data.as_slice(), |e: &Event| {
+                out.push(e.path.to_string());
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap_err();
+        assert_eq!(vec!["$.data.items[0].id"], out);
+        assert_eq!("$.data.x", err.position().path);
+    }
+
+    #[test]
+    fn test_nan_and_infinity() {
+        let data = br#"[NaN, Infinity, -Infinity, -1]"#;
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+        .allow_nan(true)
+        .build(data.as_slice(), |e: &Event| {
+            if e.typ != JsonType::Array {
+                out.push((e.typ, e.value.to_string()));
+            }
+            ControlFlow::Continue(())
+        })
+        .parse()
+        .unwrap();
+
+        let n = |v: &str| (JsonType::Number, v.to_string());
+        assert_eq!(vec![n("NaN"), n("Infinity"), n("-Infinity"), (JsonType::Integer, "-1".to_string())], out);
+        assert!(parse_number("NaN").unwrap().is_nan());
+        assert_eq!(Ok(f64::INFINITY), parse_number("Infinity"));
+        assert_eq!(Ok(f64::NEG_INFINITY), parse_number("-Infinity"));
+
+        let lenient = |data: &[u8]| {
+            JsonParserBuilder::new().allow_nan(true).build(data, |_: &Event| ControlFlow::Continue(())).parse()
+        };
+        assert!(lenient(b"Nan").is_err());
+        assert!(lenient(b"-Inf").is_err());
+        assert!(lenient(b"-").is_err());
+    }
+
+    #[test]
+    fn test_nan_and_infinity_strict() {
+        assert!(events(b"NaN").unwrap_err().starts_with("expected a value but found 'N'"));
+        assert!(events(b"Infinity").unwrap_err().starts_with("expected a value but found 'I'"));
+        assert!(events(b"-Infinity").unwrap_err().starts_with("invalid number '-'"));
+    }
+
+    #[test]
+    fn test_nested_array_paths() {
+        let out: Vec<_> = events(b"[[1,2],[3,4]]").unwrap().into_iter().map(|(p, _, v)| format!("{}={}", p, v)).collect();
+        assert_eq!(vec!["$[0][0]=1", "$[0][1]=2", "$[0]=", "$[1][0]=3", "$[1][1]=4", "$[1]=", "$="], out);
+
+        assert_eq!(
+            vec!["$[0][0][0]", "$[0][0]", "$[0][1].a", "$[0][1]", "$[0]", "$[1]", "$"],
+            paths(br#"[[[true], {"a": null}], 2]"#),
+        );
+    }
+
+    #[test]
+    fn test_member_paths() {
+        assert_eq!(vec!["$.a.b", "$.a", "$.c", "$"], paths(br#"{"a":{"b":1},"c":2}"#));
+        assert_eq!(
+            vec!["$.long_key.x[0]", "$.long_key.x", "$.long_key", "$.b", "$['c d'].e", "$['c d']", "$.f", "$"],
+            paths(br#"{"long_key": {"x": [1]}, "b": 2, "c d": {"e": 3}, "f": 4}"#),
+        );
+    }
+
+    #[test]
+    fn test_empty_input() {
+        for data in ["", " \n\t\r "] {
+            let err = json_parse(data.as_bytes(), |_, _, _| panic!("no values expected")).unwrap_err();
+            assert!(matches!(err, JsonError::UnexpectedEof { .. }), "{:?}", err);
+            assert!(err.to_string().starts_with("empty input: expected a value"), "{}", err);
+        }
+        assert!(events(b"\xEF\xBB\xBF").is_err());
+    }
+
+    #[test]
+    fn test_strict_trailing() {
+        let parse = |data: &str, strict: bool| {
+            JsonParserBuilder::new()
+                .strict_trailing(strict)
+                .build(data.as_bytes(), |_: &Event| ControlFlow::Continue(()))
+                .parse()
+        };
+
+        assert!(matches!(parse("[1] x", true), Err(JsonError::TrailingData { .. })));
+        assert!(parse("[1] x", false).is_ok());
+        assert!(parse("{} {\"broken", false).is_ok());
+        assert!(parse("[1", false).is_err());
+    }
+
+    #[test]
+    fn test_event_depth() {
+        let data = br#"{"a": [1, {"b": 2}], "c": 3}"#;
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+        .container_events(true)
+        .build(data.as_slice(), |e: &Event| {
+            out.push(format!("{} {}", e.path, e.depth));
+            ControlFlow::Continue(())
+        })
+        .parse()
+        .unwrap();
+
+        assert_eq!(vec![
+            "$ 0", "$.a 1", "$.a[0] 2", "$.a[1] 2", "$.a[1].b 3", "$.a[1] 2", "$.a 1", "$.c 1", "$ 0",
+        ], out);
+
+        let mut depth = None;
+        JsonParser::new(b"7".as_slice(), |e: &Event| {
+            depth = Some(e.depth);
+            ControlFlow::Continue(())
+        }).parse().unwrap();
+        assert_eq!(Some(0), depth);
+    }
+
+    #[test]
+    fn test_string_chunks() {
+        let text = "0123456789\u{e9}\u{1f600}abcdef";
+        let data = format!(r#"{{"blob": "{}", "esc": "ab😀cd", "empty": ""}}"#, text);
+        let mut chunks = Vec::new();
+        let mut values = Vec::new();
+        JsonParserBuilder::new()
+        .string_chunks(4, |p, c, last| chunks.push((p.to_string(), c.to_string(), last)))
+        .build(data.as_bytes(), |e: &Event| {
+            values.push(format!("{}={}", e.path, e.value));
+            ControlFlow::Continue(())
+        })
+        .parse()
+        .unwrap();
+
+        let joined = |path: &str| chunks.iter().filter(|c| c.0 == path).map(|c| c.1.as_str()).collect::<String>();
+        assert_eq!(text, joined("$.blob"));
+        assert_eq!("ab\u{1f600}cd", joined("$.esc"));
+        assert!(chunks.iter().all(|(_, c, last)| *last || (1..8).contains(&c.len())));
+        assert!(chunks.iter().filter(|c| c.0 == "$.blob").count() > 3);
+        assert_eq!(vec!["$.empty"], chunks.iter().filter(|c| c.1.is_empty()).map(|c| c.0.as_str()).collect::<Vec<_>>());
+        assert_eq!(3, chunks.iter().filter(|c| c.2).count());
+        assert_eq!(vec!["$.blob=", "$.esc=", "$.empty=", "$="], values);
+    }
+
+    #[test]
+    fn test_string_chunks_invalid_utf8() {
+        let parse = |data: &[u8]| {
+            JsonParserBuilder::new()
+                .string_chunks(2, |_, _, _| {})
+                .build(data, |_: &Event| ControlFlow::Continue(()))
+                .parse()
+        };
+        assert!(matches!(parse(b"\"abc\xff\""), Err(JsonError::InvalidUtf8 { .. })));
+        assert!(matches!(parse(b"\"abc\xc3\""), Err(JsonError::InvalidUtf8 { .. })));
+        assert!(parse("\"\u{e9}\u{e9}\u{e9}\"".as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_first() {
+        let data = br#"{"a": [1]}[true]  "x" 12 -3.5"#;
+        let mut rest = &data[..];
+        let mut values = Vec::new();
+        let mut counts = Vec::new();
+        while !rest.is_empty() {
+            let n = json_parse_first(rest, |p, _, v| values.push(format!("{}={}", p, v))).unwrap();
+            counts.push(n);
+            rest = &rest[n..];
+            rest = &rest[rest.iter().take_while(|c| c.is_ascii_whitespace()).count()..];
+        }
+
+        assert_eq!(vec![10, 6, 3, 2, 4], counts);
+        assert_eq!(vec!["$.a[0]=1", "$.a=", "$=", "$[0]=", "$=", "$=x", "$=12", "$=-3.5"], values);
+        assert!(json_parse_first(b"[1".as_slice(), |_, _, _| {}).is_err());
+    }
+
+    #[test]
+    fn test_max_string_len() {
+        let parse = |data: &str| {
+            JsonParserBuilder::new()
+                .max_string_len(5)
+                .build(data.as_bytes(), |_: &Event| ControlFlow::Continue(()))
+                .parse()
+        };
+
+        assert!(parse(r#"["12345", "éé", {"abcde": 1}]"#).is_ok());
+        let err = parse(r#"["123456"]"#).unwrap_err();
+        assert!(matches!(err, JsonError::StringTooLong { max: 5, .. }), "{:?}", err);
+        assert_eq!("string longer than 5 bytes at $[0], line 1 column 8", err.to_string());
+        assert!(matches!(parse(r#"{"abcdef": 1}"#), Err(JsonError::StringTooLong { .. })));
+        assert!(matches!(parse(r#""ééé""#), Err(JsonError::StringTooLong { .. })));
+        assert!(matches!(parse(&format!("\"{}", "x".repeat(100_000))), Err(JsonError::StringTooLong { .. })));
+
+        assert!(events(format!("\"{}\"", "x".repeat(100_000)).as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_max_container_len() {
+        let parse = |data: &str| {
+            JsonParserBuilder::new()
+                .max_array_elements(2)
+                .max_object_members(2)
+                .build(data.as_bytes(), |_: &Event| ControlFlow::Continue(()))
+                .parse()
+        };
+
+        assert!(parse(r#"[[1, 2], {"a": [], "b": {"c": 3, "d": 4}}]"#).is_ok());
+        let err = parse(r#"{"a": [1, 2, 3]}"#).unwrap_err();
+        assert!(matches!(err, JsonError::TooManyElements { max: 2, .. }), "{:?}", err);
+        assert_eq!("array has more than 2 elements at $.a, line 1 column 14", err.to_string());
+        let err = parse(r#"[0, {"a": 1, "b": 2, "c": 3}]"#).unwrap_err();
+        assert!(matches!(err, JsonError::TooManyMembers { max: 2, .. }), "{:?}", err);
+        assert_eq!("object has more than 2 members at $[1], line 1 column 22", err.to_string());
+
+        assert!(events(format!("[{}]", vec!["1"; 1000].join(",")).as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_max_total_bytes() {
+        let parse = |data: &str, max: usize| {
+            JsonParserBuilder::new()
+                .max_total_bytes(max)
+                .build(Trickle(data.as_bytes()), |_: &Event| ControlFlow::Continue(()))
+                .parse()
+        };
+
+        let data = r#"[1, 22, {"a": "b"}]"#;
+        assert!(parse(data, data.len()).is_ok());
+        let err = parse(data, data.len() - 1).unwrap_err();
+        assert!(matches!(err, JsonError::InputTooLarge { max, .. } if max == data.len() - 1), "{:?}", err);
+        assert!(err.to_string().starts_with("input size limit exceeded"), "{}", err);
+
+        assert!(parse("12345", 5).is_ok());
+        assert!(parse("12345 ", 5).is_err());
+        assert!(parse("12345", 4).is_err());
+    }
+
+    #[test]
+    fn test_push_index() {
+        for i in [0, 7, 10, 99, 100, 123456, usize::MAX] {
+            let mut path = String::from("$");
+            push_index(&mut path, i);
+            assert_eq!(format!("$[{}]", i), path);
+        }
+    }
+
+    #[test]
+    fn test_collect_events() {
+        let ev = |p: &str, t, v: &str| (p.to_string(), t, v.to_string());
+        assert_eq!(vec![ev("$", JsonType::Null, "")], collect_events(b"null".as_slice()).unwrap());
+        assert_eq!(vec![
+            ev("$.a", JsonType::String, "x"),
+            ev("$.b[0]", JsonType::Number, "1.5"),
+            ev("$.b[1]", JsonType::False, ""),
+            ev("$.b", JsonType::Array, ""),
+            ev("$", JsonType::Object, ""),
+        ], collect_events(br#"{"a": "x", "b": [1.5, false]}"#.as_slice()).unwrap());
+        assert_eq!(vec![
+            ev("$[0]", JsonType::Object, ""),
+            ev("$[1][0]", JsonType::Integer, "-7"),
+            ev("$[1]", JsonType::Array, ""),
+            ev("$", JsonType::Array, ""),
+        ], collect_events(b"[{}, [-7]]".as_slice()).unwrap());
+
+        let mut collected = Vec::new();
+        json_parse(Trickle(b"[1, {\"k\": true}]"), |p, t, v| collected.push((p.to_string(), t, v.to_string()))).unwrap();
+        assert_eq!(collected, collect_events(Trickle(b"[1, {\"k\": true}]")).unwrap());
+
+        assert!(matches!(collect_events(b"[1,]".as_slice()), Err(JsonError::UnexpectedChar { .. })));
+    }
+
+    #[test]
+    fn test_subtree() {
+        let data = br#"{"config": {"name": "x", "database": {"host": "h", "ports": [1, 2]}, "databases": 3}, "database": 4}"#;
+        let subtree = |data: &[u8], prefix: &str| {
+            let mut out = Vec::new();
+            JsonParserBuilder::new()
+                .subtree(prefix)
+                .build(data, |e: &Event| {
+                    out.push(e.path.to_string());
+                    ControlFlow::Continue(())
+                })
+                .parse()
+                .map(|_| out)
+        };
+
+        assert_eq!(
+            vec!["$.config.database.host", "$.config.database.ports[0]", "$.config.database.ports[1]", "$.config.database.ports", "$.config.database"],
+            subtree(data, "$.config.database").unwrap(),
+        );
+        assert_eq!(vec!["$.config.database.ports[1]"], subtree(data, "$.config.database.ports[1]").unwrap());
+        assert_eq!(paths(data), subtree(data, "$").unwrap());
+        assert!(subtree(data, "$.missing").unwrap().is_empty());
+
+        let err = subtree(br#"{"config": {"database": {"host": "h"}}, "other": [1, }"#, "$.config.database").unwrap_err();
+        assert!(err.to_string().starts_with("expected a value but found '}'"), "{}", err);
+
+        let keys = std::cell::RefCell::new(Vec::new());
+        let mut numbers = 0;
+        let mut out = Vec::new();
+        JsonParserBuilder::new()
+            .subtree("$.config.database")
+            .on_key(|path, key| keys.borrow_mut().push(format!("{}/{}", path, key)))
+            .on_number_text(|text| {
+                numbers += 1;
+                Cow::Borrowed(text)
+            })
+            .build(data.as_slice(), |e: &Event| {
+                out.push(e.path.to_string());
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        assert_eq!(vec!["$/config", "$.config/database", "$.config.database/host", "$.config.database/ports"], keys.into_inner());
+        assert_eq!(2, numbers);
+        assert_eq!(subtree(data, "$.config.database").unwrap(), out);
+    }
+
+    #[test]
+    fn test_number_leading_zeros() {
+        for n in ["01", "007", "-0123", "00", "-00.5", "01e1"] {
+            let err = events(n.as_bytes()).unwrap_err();
+            assert!(err.starts_with(&format!("invalid number '{}': leading zeros not allowed", n)), "{}: {}", n, err);
+        }
+        for n in ["0", "-0", "0.5", "0e1", "0.05", "10", "100.001"] {
+            assert!(events(n.as_bytes()).is_ok(), "{}", n);
+        }
+        assert!(parse_number("007").unwrap_err().ends_with("leading zeros not allowed"));
+    }
+
+    #[test]
+    fn test_number_incomplete_at_eof() {
+        for n in ["-", "1e", "1.", "1e+", "1E-", "-0.", "[1.", "{\"a\": 2e"] {
+            let mut emitted = Vec::new();
+            let err = json_parse(n.as_bytes(), |_, t, v| emitted.push((t, v.to_string()))).unwrap_err();
+            assert!(matches!(err, JsonError::InvalidNumber { .. }), "{}: {:?}", n, err);
+            assert_eq!(n.len(), err.offset(), "{}", n);
+            assert!(emitted.is_empty(), "{}: {:?}", n, emitted);
+        }
+
+        let mut it = json_events(b"[1, 2.".as_slice());
+        assert!(it.next().unwrap().is_ok());
+        assert!(matches!(it.next(), Some(Err(JsonError::InvalidNumber { .. }))));
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn test_lowercase_keys() {
+        let data = r#"{"Name": "X", "ÉTÉ": {"Inner Key": [true]}}"#;
+        let mut out = Vec::new();
+        let mut keys = Vec::new();
+        JsonParserBuilder::new()
+        .lowercase_keys(true)
+        .on_key(|_, k| keys.push(k.to_string()))
+        .build(data.as_bytes(), |e: &Event| {
+            out.push(format!("{} {:?} {}", e.path, e.key, e.value));
+            ControlFlow::Continue(())
+        })
+        .parse()
+        .unwrap();
+
+        assert_eq!(vec![
+            "$.name Some(\"Name\") X",
+            "$.été['inner key'][0] None ",
+            "$.été['inner key'] Some(\"Inner Key\") ",
+            "$.été Some(\"ÉTÉ\") ",
+            "$ None ",
+        ], out);
+        assert_eq!(vec!["Name", "ÉTÉ", "Inner Key"], keys);
+        assert_eq!(vec!["$.Name", "$"], paths(br#"{"Name": "x"}"#));
+    }
+
+    #[test]
+    fn test_stats() {
+        let data = br#"{"a": [1, 2.5, "x", true, false, null], "b": {"c": [[], {}]}, "d": "y"}"#;
+        let ignore: IgnoreEvent = |_| ControlFlow::Continue(());
+        let mut parser = JsonParserBuilder::new()
+            .filter("$.d")
+            .build(data.as_slice(), ignore);
+        parser.parse().unwrap();
+        let stats = parser.stats();
+        assert_eq!(ParseStats {
+            objects: 3,
+            arrays: 3,
+            strings: 2,
+            numbers: 2,
+            booleans: 2,
+            nulls: 1,
+            max_depth: 4,
+        }, stats);
+
+        let mut fed = JsonParser::new(Feed::default(), ignore);
+        for b in data {
+            fed.feed(&[*b]).unwrap();
+        }
+        fed.close().unwrap();
+        assert_eq!(stats, fed.stats());
+
+        parser.reset(b"1".as_slice());
+        assert_eq!(ParseStats::default(), parser.stats());
+        parser.parse().unwrap();
+        assert_eq!(ParseStats { numbers: 1, ..ParseStats::default() }, parser.stats());
+    }
+
+    #[test]
+    fn test_capacity_hints() {
+        let ignore: IgnoreEvent = |_| ControlFlow::Continue(());
+        let mut parser = JsonParserBuilder::new()
+            .expected_depth(10)
+            .value_capacity(100)
+            .build(br#"{"a": [[1, "x"]]}"#.as_slice(), ignore);
+        assert!(parser.path.capacity() >= 160);
+        assert!(parser.value.capacity() >= 100);
+        assert!(parser.stack.capacity() >= 10);
+        parser.parse().unwrap();
+        assert_eq!("$", parser.path);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut out = Vec::new();
+        let docs: [&[u8]; 3] = [br#"{"a": [1, "x"]}"#, b"\n  2", b"[1, "];
+        let mut parser = JsonParser::new(docs[0], |e: &Event| {
+            out.push(format!("{} {} {}", e.path, e.value, e.offset));
+            ControlFlow::Continue(())
+        });
+        parser.parse().unwrap();
+        parser.reset(docs[1]);
+        parser.parse().unwrap();
+        parser.reset(docs[2]);
+        let err = parser.parse().unwrap_err();
+        assert_eq!((1, 4), (err.position().line, err.position().column));
+        parser.reset(docs[0]);
+        parser.parse().unwrap();
+
+        let first = vec!["$.a[0] 1 7", "$.a[1] x 10", "$.a  6", "$  0"];
+        let mut expected = first.clone();
+        expected.extend(["$ 2 3", "$[0] 1 1"]);
+        expected.extend(first);
+        assert_eq!(expected, out);
+
+        let keys = std::cell::RefCell::new(Vec::new());
+        let mut out = Vec::new();
+        let mut parser = JsonParserBuilder::new()
+            .on_key(|path, key| keys.borrow_mut().push(format!("{}/{}", path, key)))
+            .string_chunks(4, |_, chunk, _| keys.borrow_mut().push(chunk.to_string()))
+            .reject_duplicate_keys(true)
+            .build(Feed::default(), |e: &Event| {
+                out.push(e.path.to_string());
+                ControlFlow::Continue(())
+            });
+        assert_eq!(Status::Pending, parser.feed(br#"{"a": {"b": "long string"#).unwrap());
+        parser.reset(Feed::default());
+        parser.feed(br#"{"a": "x"}"#).unwrap();
+        parser.close().unwrap();
+        drop(parser);
+        assert_eq!(vec!["$/a", "$.a/b", "long", " str", "$/a", "x"], keys.into_inner());
+        assert_eq!(vec!["$.a", "$"], out);
+    }
+
+    #[test]
+    fn test_container_len() {
+        let lens = |data: &[u8]| {
+            let mut out = Vec::new();
+            JsonParserBuilder::new()
+                .container_events(true)
+                .build(data, |e: &Event| {
+                    if matches!(e.typ, JsonType::ArrayEnd | JsonType::ObjectEnd) {
+                        out.push((e.path.to_string(), e.len));
+                    } else {
+                        assert_eq!(0, e.len);
+                    }
+                    ControlFlow::Continue(())
+                })
+                .parse()
+                .unwrap();
+            out
+        };
+
+        assert_eq!(vec![("$".to_string(), 0)], lens(b"[]"));
+        assert_eq!(vec![("$".to_string(), 1)], lens(b"[null]"));
+        assert_eq!(vec![("$".to_string(), 5)], lens(b"[1, 2, 3, 4, 5]"));
+        assert_eq!(vec![
+            ("$[0][1]".to_string(), 2),
+            ("$[0]".to_string(), 2),
+            ("$[1]".to_string(), 0),
+            ("$[2].a[0]".to_string(), 0),
+            ("$[2].a[1]".to_string(), 0),
+            ("$[2].a".to_string(), 3),
+            ("$[2]".to_string(), 2),
+            ("$".to_string(), 3),
+        ], lens(br#"[[1, [2, 3]], {}, {"a": [{}, [], 0], "b": 1}]"#));
+
+        let data = format!("[{}]", vec!["0"; 1000].join(","));
+        assert_eq!(vec![("$".to_string(), 1000)], lens(data.as_bytes()));
+    }
+
+    #[test]
+    fn test_empty_containers() {
+        let empty = |data: &[u8], container_events: bool| {
+            let mut out = Vec::new();
+            JsonParserBuilder::new()
+                .container_events(container_events)
+                .build(data, |e: &Event| {
+                    out.push((e.typ, e.empty));
+                    ControlFlow::Continue(())
+                })
+                .parse()
+                .unwrap();
+            out
+        };
+
+        assert_eq!(vec![(JsonType::Object, true)], empty(b"{}", false));
+        assert_eq!(vec![(JsonType::Array, true)], empty(b"[ ]", false));
+        assert_eq!(vec![(JsonType::Integer, false), (JsonType::Object, false)], empty(br#"{"a":1}"#, false));
+        assert_eq!(vec![(JsonType::Integer, false), (JsonType::Array, false)], empty(b"[1]", false));
+        assert_eq!(vec![(JsonType::Array, true), (JsonType::Array, false)], empty(b"[[]]", false));
+        assert_eq!(vec![
+            (JsonType::ObjectStart, false),
+            (JsonType::ArrayStart, false),
+            (JsonType::ArrayEnd, true),
+            (JsonType::ObjectEnd, false),
+        ], empty(br#"{"a": []}"#, true));
+    }
+
+    #[test]
+    fn test_skip_value() {
+        let data = br#"{"a": 1, "b": {"c": [1, {"d": "x"}], "e": {}}, "f": [true]}"#;
+        let mut it = json_events(data.as_slice());
+        assert_eq!(("$.a".to_string(), JsonType::Integer, "1".to_string()), it.next().unwrap().unwrap());
+        it.skip_value().unwrap();
+        assert_eq!(("$.f[0]".to_string(), JsonType::True, String::new()), it.next().unwrap().unwrap());
+        assert!(matches!(it.skip_value(), Err(JsonError::UnexpectedChar { .. })));
+        assert_eq!(("$.f".to_string(), JsonType::Array, String::new()), it.next().unwrap().unwrap());
+        assert_eq!(("$".to_string(), JsonType::Object, String::new()), it.next().unwrap().unwrap());
+        assert!(matches!(it.skip_value(), Err(JsonError::UnexpectedEof { .. })));
+
+        let mut keys = Vec::new();
+        let mut out = Vec::new();
+        let mut parser = JsonParserBuilder::new()
+            .container_events(true)
+            .on_key(|_, k| keys.push(k.to_string()))
+            .build(br#"[{"a": [1]}, {"b": 2}]"#.as_slice(), |e: &Event| {
+                out.push(e.path.to_string());
+                ControlFlow::Continue(())
+            });
+        parser.skip_value().unwrap();
+        parser.parse().unwrap();
+        drop(parser);
+        assert!(keys.is_empty());
+        assert!(out.is_empty());
+
+        let mut out = Vec::new();
+        let mut it = json_events(b"[[1, [2]], 3]".as_slice());
+        while let Some(e) = it.next() {
+            let (path, _, _) = e.unwrap();
+            if path == "$[0][0]" {
+                it.skip_value().unwrap();
+            }
+            out.push(path);
+        }
+        assert_eq!(vec!["$[0][0]", "$[0]", "$[1]", "$"], out);
+    }
+
+    #[test]
+    fn test_on_number_text() {
+        let mut out = Vec::new();
+        let mut seen = Vec::new();
+        JsonParserBuilder::new()
+            .on_number_text(|s| {
+                seen.push(s.to_string());
+                match s.contains("e+") {
+                    true => Cow::Owned(s.replace("e+", "e")),
+                    false => Cow::Borrowed(s),
+                }
+            })
+            .build(b"[1e+5, 2.5E+1, 3]".as_slice(), |e: &Event| {
+                out.push((e.typ, e.value.to_string()));
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        assert_eq!(vec!["1e+5", "2.5E+1", "3"], seen);
+        assert_eq!(vec![
+            (JsonType::Number, "1e5".to_string()),
+            (JsonType::Number, "2.5E+1".to_string()),
+            (JsonType::Integer, "3".to_string()),
+            (JsonType::Array, String::new()),
+        ], out);
+
+        let mut parser = JsonParserBuilder::new()
+            .on_number_text(|_| Cow::Borrowed("1.5."))
+            .build(b"1".as_slice(), |_: &Event| ControlFlow::Continue(()));
+        assert!(matches!(parser.parse(), Err(JsonError::InvalidNumber { .. })));
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let slice_events = |data: &[u8], builder: JsonParserBuilder| {
+            let mut out = Vec::new();
+            builder
+                .build_slice(data, |e: &Event| {
+                    out.push((e.path.to_string(), e.typ, e.value.to_string()));
+                    ControlFlow::Continue(())
+                })
+                .parse()
+                .map(|_| out)
+                .map_err(|e| e.to_string())
+        };
+        let docs: [&[u8]; 12] = [
+            br#"{"a": ["x", "y\"z", "\u00e9t\u00e9"], "b": {"c": -1.5e3}, "": ""}"#,
+            "[\"caf\u{e9}\", \"\u{1F600}\"]".as_bytes(),
+            b"\"\xff\"",
+            b"[\"a\nb\"]",
+            b"\n  \"abc",
+            b"[\"abc\\",
+            b"\"\\q\"",
+            br#"{"a": "b" "c"}"#,
+            b"[1, 2,]",
+            b"[]",
+            b"\"\"",
+            b"",
+        ];
+        for data in docs {
+            assert_eq!(events(data), slice_events(data, JsonParserBuilder::new()));
+        }
+
+        let limited = || JsonParserBuilder::new().max_string_len(3).max_total_bytes(12);
+        for data in [br#"["abc"]"#.as_slice(), br#"["abcd"]"#, br#"["a", "b\nc"]"#, br#"["ab", "cdef"]"#] {
+            let mut out = Vec::new();
+            let via_reader = limited()
+                .build(data, |e: &Event| {
+                    out.push((e.path.to_string(), e.typ, e.value.to_string()));
+                    ControlFlow::Continue(())
+                })
+                .parse()
+                .map(|_| out)
+                .map_err(|e| e.to_string());
+            assert_eq!(via_reader, slice_events(data, limited()));
+        }
+
+        let data = br#"["plain", "esc\"aped", {"key": "v"}]"#;
+        let mut borrowed = Vec::new();
+        JsonParser::from_slice(data, |e: &Event| {
+            if e.typ == JsonType::String {
+                borrowed.push((e.value.to_string(), data.as_ptr_range().contains(&e.value.as_ptr())));
+            }
+            ControlFlow::Continue(())
+        }).parse().unwrap();
+        assert_eq!(vec![("plain".to_string(), true), ("esc\"aped".to_string(), false), ("v".to_string(), true)], borrowed);
+    }
+
+    #[test]
+    fn test_letters_after_number() {
+        let err = collect_events(b"42abc".as_slice()).unwrap_err();
+        assert!(matches!(err, JsonError::UnexpectedChar { .. }));
+        assert_eq!("unexpected 'a' after number at $, line 1 column 3", err.to_string());
+        assert_eq!("unexpected 'x' after number at $[1], line 1 column 8", events(b"[1, 2.5x]").unwrap_err());
+        assert_eq!("unexpected 'a' after number at $.a, line 1 column 8", events(br#"{"a": 1a}"#).unwrap_err());
+        assert!(matches!(collect_events(b"42 abc".as_slice()), Err(JsonError::TrailingData { .. })));
+
+        let mut out = Vec::new();
+        assert_eq!(2, json_parse_first(b"42abc".as_slice(), |_, _, v| out.push(v.to_string())).unwrap());
+        assert_eq!(vec!["42"], out);
+
+        let mut out = Vec::new();
+        let err = json_parse(b"[1, 2x]".as_slice(), |p, _, v| out.push(format!("{}={}", p, v))).unwrap_err();
+        assert_eq!("unexpected 'x' after number at $[1], line 1 column 6", err.to_string());
+        assert_eq!(vec!["$[0]=1", "$[1]=2"], out);
+        let err = json_parse_lines(b"1\n2x\n".as_slice(), |_, _, _| {}).unwrap_err();
+        assert_eq!("record 2: unexpected 'x' after number at $, line 2 column 2", err.to_string());
+        let err = json_parse_stream(b"1 2x".as_slice(), |_, _, _| {}).unwrap_err();
+        assert_eq!("unexpected 'x' after number at $, line 1 column 4", err.to_string());
+    }
+
+    #[test]
+    fn test_error_conversions() {
+        fn sum(data: &[u8]) -> Result<f64, Box<dyn std::error::Error>> {
+            let mut total = 0.0;
+            let mut numbers = Vec::new();
+            json_parse(data, |_, t, v| if t == JsonType::Integer || t == JsonType::Number {
+                numbers.push(v.to_string());
+            })?;
+            for n in numbers {
+                total += parse_number(&n)?;
+            }
+            Ok(total)
+        }
+        assert_eq!(6.5, sum(b"[1, 2.5, {\"a\": 3}]").unwrap());
+        let err = sum(b"[1, x]").unwrap_err();
+        assert!(err.downcast_ref::<JsonError>().is_some());
+
+        fn read(reader: &mut impl io::Read) -> JsonResult<String> {
+            let mut s = String::new();
+            reader.read_to_string(&mut s)?;
+            Ok(s)
+        }
+        let err = read(&mut Broken).unwrap_err();
+        assert!(matches!(&err, JsonError::Io { error, .. } if error.to_string() == "broken"));
+        assert_eq!(&Position::default(), err.position());
+    }
+
+    #[test]
+    fn test_parse_next() {
+        let mut out = Vec::new();
+        let mut parser = JsonParser::new(b" 1 [true, \"a\"]\n{\"b\": null}  ".as_slice(), |e: &Event| {
+            out.push(e.path.to_string());
+            ControlFlow::Continue(())
+        });
+        let mut values = 0;
+        while parser.parse_next().unwrap() {
+            values += 1;
+        }
+        assert!(!parser.parse_next().unwrap());
+        drop(parser);
+        assert_eq!(3, values);
+        assert_eq!(vec!["$", "$[0]", "$[1]", "$", "$.b", "$"], out);
+
+        let mut out = Vec::new();
+        let mut parser = JsonParser::new(b"[1, 2] 3".as_slice(), |e: &Event| {
+            out.push(e.path.to_string());
+            if e.path == "$[0]" {
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        });
+        assert!(parser.parse_next().unwrap());
+        assert!(parser.parse_next().unwrap());
+        assert!(parser.parse_next().unwrap());
+        assert!(!parser.parse_next().unwrap());
+        drop(parser);
+        assert_eq!(vec!["$[0]", "$[1]", "$", "$"], out);
+
+        let mut parser = JsonParser::new(b"\xEF\xBB\xBF ".as_slice(), |_: &Event| ControlFlow::Continue(()));
+        assert!(!parser.parse_next().unwrap());
+        let mut parser = JsonParser::new(b"1 ]".as_slice(), |_: &Event| ControlFlow::Continue(()));
+        assert!(parser.parse_next().unwrap());
+        assert!(matches!(parser.parse_next(), Err(JsonError::UnexpectedChar { .. })));
+    }
+
+    #[test]
+    fn test_cancel_flag() {
+        use std::sync::Arc;
+        use std::sync::atomic::AtomicBool;
+
+        let data = format!("[{}]", (0..20_000).map(|i| format!("\"item {}\"", i)).collect::<Vec<_>>().join(","));
+        for slice in [false, true] {
+            let flag = Arc::new(AtomicBool::new(false));
+            let mut count = 0;
+            let on_value = |_: &Event| {
+                count += 1;
+                if count == 10 {
+                    flag.store(true, Ordering::Relaxed);
+                }
+                ControlFlow::Continue(())
+            };
+            let builder = JsonParserBuilder::new().cancel_flag(flag.clone());
+            let err = match slice {
+                false => builder.build(data.as_bytes(), on_value).parse(),
+                true => builder.build_slice(data.as_bytes(), on_value).parse(),
+            }.unwrap_err();
+            assert!(matches!(err, JsonError::Cancelled { .. }), "{:?}", err);
+            assert!(err.offset() <= 2 * CANCEL_CHECK_INTERVAL);
+            assert!(count < 1000);
+        }
+
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut parser = JsonParserBuilder::new()
+            .cancel_flag(flag)
+            .build(data.as_bytes(), |_: &Event| ControlFlow::Continue(()));
+        assert!(parser.parse().is_ok());
+    }
+
+    #[test]
+    fn test_current_path_and_value() {
+        let mut parser = JsonParser::new(br#"{"a": [1, "x\ny"], "b": true}"#.as_slice(), |e: &Event| {
+            match e.typ {
+                JsonType::String | JsonType::True => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        });
+        assert_eq!("$", parser.current_path());
+        assert!(parser.parse_next().unwrap());
+        assert_eq!(("$.a[1]", "x\ny"), (parser.current_path(), parser.current_value()));
+        assert!(parser.parse_next().unwrap());
+        assert_eq!(("$.b", ""), (parser.current_path(), parser.current_value()));
+        parser.parse().unwrap();
+        assert_eq!(("$", ""), (parser.current_path(), parser.current_value()));
+
+        let data = br#"["borrowed"]"#;
+        let mut parser = JsonParser::from_slice(data, |_: &Event| ControlFlow::Break(()));
+        parser.parse().unwrap();
+        assert_eq!(("$[0]", "borrowed"), (parser.current_path(), parser.current_value()));
+    }
+
+    #[test]
+    fn test_max_value_len() {
+        let parse = |data: &[u8]| {
+            JsonParserBuilder::new()
+                .max_value_len(5)
+                .build(data, |_: &Event| ControlFlow::Continue(()))
+                .parse()
+                .map_err(|e| e.to_string())
+        };
+        assert!(parse(br#"{"a long key": ["abcde", 12345, -1.25]}"#).is_ok());
+        assert_eq!(Err("value longer than 5 bytes at $.n[1], line 1 column 16".to_string()), parse(br#"{"n": [1, 123456]}"#));
+        assert_eq!(Err("value longer than 5 bytes at $.s, line 1 column 13".to_string()), parse(br#"{"s": "abcdef"}"#));
+        assert_eq!(Err("value longer than 5 bytes at $, line 1 column 9".to_string()), parse(br#""ab\n\ncdef""#));
+        assert!(parse(format!("[{}]", "1".repeat(100_000)).as_bytes()).is_err());
+
+        let err = JsonParserBuilder::new()
+            .max_value_len(5)
+            .max_string_len(3)
+            .build(br#""abcdef""#.as_slice(), |_: &Event| ControlFlow::Continue(()))
+            .parse()
+            .unwrap_err();
+        assert!(matches!(err, JsonError::StringTooLong { max: 3, .. }));
+    }
+
+    #[test]
+    fn test_bytes_consumed() {
+        let ignore = |_: &Event| ControlFlow::Continue(());
+        for data in [br#" {"a": [1, "x"]} "#.as_slice(), b"12", b"\xEF\xBB\xBF[]\n"] {
+            let mut parser = JsonParser::new(data, ignore);
+            assert_eq!(0, parser.bytes_consumed());
+            parser.parse().unwrap();
+            assert_eq!(data.len(), parser.bytes_consumed());
+        }
+
+        let mut parser = JsonParserBuilder::new().strict_trailing(false).build(b"[1] [2]".as_slice(), ignore);
+        parser.parse().unwrap();
+        assert_eq!(3, parser.bytes_consumed());
+
+        let mut parser = JsonParser::new(b"1 23".as_slice(), ignore);
+        assert!(parser.parse_next().unwrap());
+        assert_eq!(1, parser.bytes_consumed());
+        assert!(parser.parse_next().unwrap());
+        assert_eq!(4, parser.bytes_consumed());
+        parser.reset(b"[]".as_slice());
+        assert_eq!(0, parser.bytes_consumed());
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        let parse = |data: &[u8], raw: bool, slice: bool| {
+            let mut out = Vec::new();
+            let on_value = |e: &Event| {
+                if e.typ == JsonType::String {
+                    out.push(format!("{}={}", e.path, e.value));
+                }
+                ControlFlow::Continue(())
+            };
+            let builder = JsonParserBuilder::new().raw_strings(raw).max_string_len(8);
+            match slice {
+                false => builder.build(data, on_value).parse(),
+                true => builder.build_slice(data, on_value).parse(),
+            }.map_err(|e| e.to_string())?;
+            Ok::<_, String>(out)
+        };
+
+        let data = r#"{"a\nb": "a\nb", "q": "x\"y\\", "u": "é\q"}"#.as_bytes();
+        for slice in [false, true] {
+            let raw = vec!["$['a\nb']=a\\nb".to_string(), r#"$.q=x\"y\\"#.to_string(), r"$.u=é\q".to_string()];
+            assert_eq!(Ok(raw), parse(data, true, slice));
+            assert_eq!(Err("string longer than 8 bytes at $, line 1 column 11".to_string()), parse(br#""\\\\\\\\\""#, true, slice));
+            assert_eq!(Err("unterminated string at $, line 1 column 4".to_string()), parse(br#""a\""#, true, slice));
+            assert!(parse(b"\"a\\\n\"", true, slice).unwrap_err().starts_with("unescaped control character"));
+        }
+        assert_eq!(Ok(vec!["$=a\nb".to_string()]), parse(br#""a\nb""#, false, true));
+        assert_eq!(Ok(vec![r"$=a\nb".to_string()]), parse(br#""a\nb""#, true, true));
+    }
+
+    #[test]
+    fn test_single_quotes() {
+        let parse = |data: &[u8], lenient: bool, slice: bool| {
+            let mut out = Vec::new();
+            let on_value = |e: &Event| {
+                out.push(format!("{} {:?} {}", e.path, e.typ, e.value));
+                ControlFlow::Continue(())
+            };
+            let builder = JsonParserBuilder::new().single_quotes(lenient);
+            match slice {
+                false => builder.build(data, on_value).parse(),
+                true => builder.build_slice(data, on_value).parse(),
+            }.map_err(|e| e.to_string())?;
+            Ok::<_, String>(out)
+        };
+
+        for slice in [false, true] {
+            let err = "strings must use double quotes, not single quotes at $, line 1 column 2".to_string();
+            assert_eq!(Err(err), parse(b"{'a': 1}", false, slice));
+            let err = "strings must use double quotes, not single quotes at $.a, line 1 column 7".to_string();
+            assert_eq!(Err(err), parse(br#"{"a": 'b'}"#, false, slice));
+            assert!(parse(br#"["it\'s"]"#, false, slice).unwrap_err().starts_with("invalid escape"));
+
+            let out = parse(br#"{'a': 'say "hi"', 'it\'s': ["x", 'y\n']}"#, true, slice).unwrap();
+            let expected = vec![
+                r#"$.a String say "hi""#,
+                r"$['it\'s'][0] String x",
+                "$['it\\'s'][1] String y\n",
+                r"$['it\'s'] Array ",
+                "$ Object ",
+            ];
+            assert_eq!(expected, out);
+            assert_eq!(Err("unterminated string at $, line 1 column 4".to_string()), parse(b"'abc", true, slice));
+        }
+    }
+
+    #[test]
+    fn test_crlf_lines() {
+        let err = collect_events(b"{\r\n  \"a\": 1,\r\n  \"b\": x\r\n}".as_slice()).unwrap_err();
+        assert_eq!((3, 8), (err.position().line, err.position().column));
+
+        let err = collect_events(b"[1,\r\r2,\rx]".as_slice()).unwrap_err();
+        assert_eq!((1, 9), (err.position().line, err.position().column));
+
+        let err = events_with_comments(b"[1, // one\r\n\r\n 2 /* two\r\n */, x]").unwrap_err();
+        assert_eq!((4, 6), (err.position().line, err.position().column));
+
+        let mut out = Vec::new();
+        json_parse_lines(b"{\"a\": 1}\r\n\r\n[2]\r\n".as_slice(), |p, _, _| out.push(p.to_string())).unwrap();
+        assert_eq!(vec!["$.a", "$", "$[0]", "$"], out);
+        let err = json_parse_lines(b"1\r\n2\r\n3 x\r\n".as_slice(), |_, _, _| {}).unwrap_err();
+        assert_eq!((3, 3), (err.position().line, err.position().column));
+    }
+
+    #[test]
+    fn test_parse_and_discard() {
+        for data in [br#"{"a": [1, -2.5e3, "x\u00e9", true, null, {}]}"#.as_slice(), b" 0 ", b"\"\\\"\""] {
+            assert!(parse_and_discard(data).is_ok(), "{:?}", data);
+        }
+        for data in [b"".as_slice(), b"[1,]", b"{\"a\" 1}", b"\"\xff\"", b"01", b"[1] 2", b"[\"a"] {
+            assert_eq!(events(data).unwrap_err(), parse_and_discard(data).unwrap_err().to_string());
+        }
+    }
+
+    #[test]
+    fn test_on_path() {
+        let data = br#"{"config": {"port": 8080, "host": "h", "ports": [1]}, "port": 1}"#;
+        let mut port = None;
+        let mut hosts = Vec::new();
+        let mut count = 0;
+        JsonParserBuilder::new()
+            .on_path("$.config.port", |e| port = parse_i64(e.value).ok())
+            .on_path("$.config.host", |e| hosts.push(e.value.to_string()))
+            .on_path("$.missing", |_| panic!("no such value"))
+            .build(data.as_slice(), |_: &Event| {
+                count += 1;
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        assert_eq!(Some(8080), port);
+        assert_eq!(vec!["h"], hosts);
+        assert_eq!(7, count);
+
+        let seen = std::cell::RefCell::new(Vec::new());
+        JsonParserBuilder::new()
+            .on_path("$.a", |e| seen.borrow_mut().push(("first", e.typ)))
+            .on_path("$.a", |e| seen.borrow_mut().push(("second", e.typ)))
+            .filter("$.b")
+            .on_path("$.b", |e| seen.borrow_mut().push(("b", e.typ)))
+            .build(br#"{"a": [], "b": null}"#.as_slice(), |_: &Event| ControlFlow::Continue(()))
+            .parse()
+            .unwrap();
+        assert_eq!(vec![("b", JsonType::Null)], seen.into_inner());
+
+        let seen = std::cell::RefCell::new(Vec::new());
+        JsonParserBuilder::new()
+            .on_path("$.a", |_| seen.borrow_mut().push("first"))
+            .on_path("$.b", |_| seen.borrow_mut().push("b"))
+            .on_path("$.a", |_| seen.borrow_mut().push("second"))
+            .build(br#"{"a": [], "b": null}"#.as_slice(), |_: &Event| ControlFlow::Continue(()))
+            .parse()
+            .unwrap();
+        assert_eq!(vec!["first", "second", "b"], seen.into_inner());
+    }
+
+    #[test]
+    fn test_on_complete() {
+        use std::cell::Cell;
+
+        let completed = Cell::new(0);
+        let events = Cell::new(0);
+        let parser = |data: &'static [u8]| JsonParserBuilder::new()
+            .on_complete(|| {
+                assert!(events.get() > 0);
+                completed.set(completed.get() + 1);
+            })
+            .build(data, |_: &Event| {
+                events.set(events.get() + 1);
+                ControlFlow::Continue(())
+            });
+
+        parser(br#"{"a": [1, 2]}  "#).parse().unwrap();
+        assert_eq!((1, 4), (completed.get(), events.get()));
+
+        completed.set(0);
+        assert!(parser(b"[1] 2").parse().is_err());
+        assert_eq!(0, completed.get());
+
+        completed.set(0);
+        let mut p = parser(b"[1, 2");
+        assert!(p.parse().is_err());
+        drop(p);
+        assert_eq!(0, completed.get());
+
+        completed.set(0);
+        parser(b"1 [2] {}").parse_stream().unwrap();
+        assert_eq!(3, completed.get());
+
+        completed.set(0);
+        assert!(parser(b"[1, 2] ").parse_recovering().is_empty());
+        assert_eq!(1, completed.get());
+        assert_eq!(1, parser(b"[1, x, 3]").parse_recovering().len());
+        assert_eq!(2, completed.get());
+        assert_eq!(1, parser(b"[1] x").parse_recovering().len());
+        assert_eq!(2, completed.get());
+
+        completed.set(0);
+        let mut p = JsonParserBuilder::new()
+            .on_complete(|| completed.set(completed.get() + 1))
+            .build(Feed::default(), |_: &Event| ControlFlow::Continue(()));
+        for chunk in [b"[1, ".as_slice(), b"2] ", b" "] {
+            p.feed(chunk).unwrap();
+        }
+        p.close().unwrap();
+        drop(p);
+        assert_eq!(1, completed.get());
+    }
+
+    #[test]
+    fn test_number_underscores() {
+        let parse = |data: &[u8]| {
+            let mut out = Vec::new();
+            JsonParserBuilder::new()
+                .number_underscores(true)
+                .build(data, |e: &Event| {
+                    out.push((e.typ, e.value.to_string()));
+                    ControlFlow::Continue(())
+                })
+                .parse()
+                .map(|_| out)
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(Ok(vec![(JsonType::Integer, "1000000".to_string())]), parse(b"1_000_000"));
+        assert_eq!(Ok(vec![(JsonType::Number, "-12.3456e10".to_string())]), parse(b"-1_2.34_56e1_0"));
+        for bad in ["1_", "1__0", "-_1", "1_.5", "1._5", "1e_5", "1_e5", "1.5_"] {
+            let err = parse(bad.as_bytes()).unwrap_err();
+            assert!(err.starts_with(&format!("invalid number '{}': '_' must be between digits", bad)), "{}: {}", bad, err);
+        }
+        assert!(parse(b"0_1").unwrap_err().contains("leading zeros"));
+        assert!(parse(b"_1").unwrap_err().starts_with("expected a value but found '_'"));
+
+        assert!(events(b"1_000").is_err());
+    }
+}
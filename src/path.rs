@@ -0,0 +1,127 @@
+/// Splits a path such as `$.a['b c'][0]` into its root and segments: `$`,
+/// `.a`, `['b c']` and `[0]`.
+struct Segments<'a> {
+    rest: &'a str,
+    separator: char,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let b = self.rest.as_bytes();
+        if b.is_empty() {
+            return None;
+        }
+
+        let end = if b.starts_with(b"['") {
+            let mut i = 2;
+            while i < b.len() {
+                match b[i] {
+                    b'\\' => i += 2,
+                    b'\'' if b.get(i + 1) == Some(&b']') => {
+                        i += 2;
+                        break;
+                    },
+                    _ => i += 1,
+                }
+            }
+            i.min(b.len())
+        } else {
+            let first = self.rest.chars().next().map_or(0, char::len_utf8);
+            self.rest[first..].find([self.separator, '[']).map_or(b.len(), |i| first + i)
+        };
+
+        let (segment, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(segment)
+    }
+}
+
+fn segment_matches(pattern: &str, segment: &str, separator: char) -> bool {
+    if pattern.strip_prefix(separator) == Some("*") {
+        return segment.starts_with(separator) || segment.starts_with("['");
+    }
+    match pattern {
+        "[*]" => segment.starts_with('[') && !segment.starts_with("['"),
+        _ => pattern == segment,
+    }
+}
+
+/// Checks a path built by the parser against a pattern written the same
+/// way, where `[*]` stands for any array index and `.*` for any key, `.`
+/// being the separator.
+pub(crate) fn matches(pattern: &str, path: &str, separator: char) -> bool {
+    let mut pattern = Segments { rest: pattern, separator };
+    let mut path = Segments { rest: path, separator };
+
+    loop {
+        match (pattern.next(), path.next()) {
+            (None, None) => return true,
+            (Some(p), Some(s)) if segment_matches(p, s, separator) => continue,
+            _ => return false,
+        }
+    }
+}
+
+/// Checks whether `path` is `prefix` itself or inside of it, so that
+/// `$.a` contains `$.a.b` and `$.a[0]` but not `$.ab`.
+pub(crate) fn is_within(prefix: &str, path: &str, separator: char) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with(separator) || rest.starts_with('['),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments() {
+        let split = |p| Segments { rest: p, separator: '.' }.collect::<Vec<_>>();
+        assert_eq!(vec!["$"], split("$"));
+        assert_eq!(vec!["$", ".a", "['b c']", "[0]", ".d"], split("$.a['b c'][0].d"));
+        assert_eq!(vec!["$", "['it\\'s']", "['a\\\\']"], split("$['it\\'s']['a\\\\']"));
+        assert_eq!(vec!["$", "['x.y[1]']", "[12]"], split("$['x.y[1]'][12]"));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("$", "$", '.'));
+        assert!(matches("$.a.b", "$.a.b", '.'));
+        assert!(!matches("$.a", "$.a.b", '.'));
+        assert!(!matches("$.a.b", "$.a", '.'));
+        assert!(!matches("$.a", "$.ab", '.'));
+
+        assert!(matches("$.users[*].email", "$.users[0].email", '.'));
+        assert!(matches("$.users[*].email", "$.users[15].email", '.'));
+        assert!(!matches("$.users[*].email", "$.users.x.email", '.'));
+        assert!(!matches("$.users[*].email", "$.users[0].name", '.'));
+
+        assert!(matches("$.*.id", "$.a.id", '.'));
+        assert!(matches("$.*.id", "$['a b'].id", '.'));
+        assert!(!matches("$.*.id", "$[0].id", '.'));
+        assert!(matches("$.*[*].*", "$.a[3]['x y']", '.'));
+        assert!(matches("$['a b'][*]", "$['a b'][1]", '.'));
+        assert!(matches("$['a[0]']", "$['a[0]']", '.'));
+        assert!(!matches("$.a[0]", "$['a[0]']", '.'));
+        assert!(!matches("$.a[*]", "$['a[0]']", '.'));
+        assert!(matches(r"$['\']'].b", r"$['\']'].b", '.'));
+
+        assert!(matches("/*/b", "/a/b", '/'));
+        assert!(!matches("/*/b", "/a.b", '/'));
+    }
+
+    #[test]
+    fn test_is_within() {
+        assert!(is_within("$", "$", '.'));
+        assert!(is_within("$", "$[0]", '.'));
+        assert!(is_within("$.a", "$.a.b", '.'));
+        assert!(is_within("$.a", "$.a['b c']", '.'));
+        assert!(is_within("$.a", "$.a[3].c", '.'));
+        assert!(!is_within("$.a", "$.ab", '.'));
+        assert!(!is_within("$.a.b", "$.a", '.'));
+        assert!(is_within("/a", "/a/b", '/'));
+    }
+}
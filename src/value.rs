@@ -0,0 +1,425 @@
+use std::{fmt, io};
+use std::ops::ControlFlow;
+
+use crate::{Event, JsonError, JsonParserBuilder, JsonType};
+
+/// A fully parsed JSON document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in the order they appeared in the input.
+    Object(Vec<(String, Value)>),
+}
+
+/// A number in a [`Value`], in the first of the variants that holds it
+/// exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    /// An integer that fits an `i64`.
+    Int(i64),
+    /// A positive integer that only fits a `u64`.
+    UInt(u64),
+    /// A number with a fraction or an exponent, or `-0`, as the closest
+    /// `f64`.
+    Float(f64),
+    /// A number too large for any of the above, as it was written.
+    Raw(String),
+}
+
+impl Number {
+    /// Picks the variant for `text`, a number as reported by the parser.
+    pub fn from_literal(text: &str) -> Self {
+        if !text.contains(['.', 'e', 'E']) {
+            // An integer loses the sign of negative zero.
+            if text == "-0" {
+                return Number::Float(-0.0);
+            }
+            if let Ok(i) = text.parse() {
+                return Number::Int(i);
+            }
+            if let Ok(u) = text.parse() {
+                return Number::UInt(u);
+            }
+        } else if let Ok(f) = text.parse::<f64>() && f.is_finite() {
+            return Number::Float(f);
+        }
+        Number::Raw(text.to_string())
+    }
+}
+
+/// Writes the number as JSON, floats in the shortest form that reads back
+/// as the same `f64`.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Int(i) => write!(f, "{}", i),
+            Number::UInt(u) => write!(f, "{}", u),
+            Number::Float(x) => write!(f, "{:?}", x),
+            Number::Raw(text) => f.write_str(text),
+        }
+    }
+}
+
+/// Parses a whole document into a [`Value`] tree.
+pub fn parse_to_value<T: io::Read>(reader: T) -> Result<Value, JsonError> {
+    let mut stack: Vec<(Option<String>, Value)> = Vec::new();
+    let mut root = None;
+
+    JsonParserBuilder::new()
+    .container_events(true)
+    .build(reader, |e: &Event| {
+        let (key, value) = match e.typ {
+            JsonType::ObjectStart => {
+                stack.push((e.key.map(str::to_string), Value::Object(Vec::new())));
+                return ControlFlow::Continue(());
+            },
+            JsonType::ArrayStart => {
+                stack.push((e.key.map(str::to_string), Value::Array(Vec::new())));
+                return ControlFlow::Continue(());
+            },
+            JsonType::ObjectEnd | JsonType::ArrayEnd => stack.pop().expect("unbalanced container events"),
+            JsonType::String => (e.key.map(str::to_string), Value::String(e.value.to_string())),
+            JsonType::Number | JsonType::Integer => (e.key.map(str::to_string), Value::Number(Number::from_literal(e.value))),
+            JsonType::True => (e.key.map(str::to_string), Value::Bool(true)),
+            JsonType::False => (e.key.map(str::to_string), Value::Bool(false)),
+            JsonType::Null => (e.key.map(str::to_string), Value::Null),
+            JsonType::Array | JsonType::Object => unreachable!("not reported with container events"),
+        };
+
+        match stack.last_mut() {
+            Some((_, Value::Array(items))) => items.push(value),
+            Some((_, Value::Object(members))) => members.push((key.unwrap_or_default(), value)),
+            Some(_) => unreachable!("only containers are pushed on the stack"),
+            None => root = Some(value),
+        }
+        ControlFlow::Continue(())
+    })
+    .parse()?;
+
+    Ok(root.expect("a successful parse reports the top-level value"))
+}
+
+/// Formats `value` as indented JSON, with `indent` spaces per level and
+/// object members in their original order.
+pub fn to_pretty_string(value: &Value, indent: usize) -> String {
+    let mut out = String::new();
+    write_pretty(&mut out, value, indent, 0);
+    out
+}
+
+fn write_pretty(out: &mut String, value: &Value, indent: usize, level: usize) {
+    let newline = |out: &mut String, level: usize| {
+        out.push('\n');
+        out.extend(std::iter::repeat_n(' ', indent * level));
+    };
+
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&n.to_string()),
+        Value::String(s) => write_string(out, s, false),
+        Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        Value::Object(members) if members.is_empty() => out.push_str("{}"),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                write_pretty(out, item, indent, level + 1);
+            }
+            newline(out, level);
+            out.push(']');
+        },
+        Value::Object(members) => {
+            out.push('{');
+            for (i, (key, value)) in members.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                write_string(out, key, false);
+                out.push_str(": ");
+                write_pretty(out, value, indent, level + 1);
+            }
+            newline(out, level);
+            out.push('}');
+        },
+    }
+}
+
+/// Formats `value` as JSON without any whitespace, for hashing or signing.
+///
+/// The output only depends on the data: strings use the shortest escapes,
+/// with everything outside of printable ASCII as `\uXXXX`, and numbers
+/// lose trailing zeros in the fraction and redundant exponent signs and
+/// digits, after floats are written in their shortest form. Members stay
+/// in their original order.
+pub fn to_compact_string(value: &Value) -> String {
+    let mut out = String::new();
+    write_compact(&mut out, value);
+    out
+}
+
+fn write_compact(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write_number(out, &n.to_string()),
+        Value::String(s) => write_string(out, s, true),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_compact(out, item);
+            }
+            out.push(']');
+        },
+        Value::Object(members) => {
+            out.push('{');
+            for (i, (key, value)) in members.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, key, true);
+                out.push(':');
+                write_compact(out, value);
+            }
+            out.push('}');
+        },
+    }
+}
+
+/// Writes the JSON number `n` without trailing zeros in the fraction, `+`
+/// or leading zeros in the exponent, or a zero exponent.
+fn write_number(out: &mut String, n: &str) {
+    let (mantissa, exponent) = match n.find(['e', 'E']) {
+        Some(i) => (&n[..i], Some(&n[i + 1..])),
+        None => (n, None),
+    };
+
+    match mantissa.split_once('.') {
+        Some((int, frac)) => {
+            out.push_str(int);
+            let frac = frac.trim_end_matches('0');
+            if !frac.is_empty() {
+                out.push('.');
+                out.push_str(frac);
+            }
+        },
+        None => out.push_str(mantissa),
+    }
+
+    if let Some(exponent) = exponent {
+        let (sign, digits) = match exponent.as_bytes().first() {
+            Some(b'-') => ("-", &exponent[1..]),
+            Some(b'+') => ("", &exponent[1..]),
+            _ => ("", exponent),
+        };
+        let digits = digits.trim_start_matches('0');
+        if !digits.is_empty() {
+            out.push('e');
+            out.push_str(sign);
+            out.push_str(digits);
+        }
+    }
+}
+
+/// Writes `s` as a JSON string, escaping what the parser would not accept
+/// raw: quotes, backslashes and control characters. With `ascii`, anything
+/// else outside of printable ASCII is escaped too.
+pub(crate) fn write_string(out: &mut String, s: &str, ascii: bool) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\0'..='\u{1f}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c if ascii && !(' '..='~').contains(&c) => {
+                for u in c.encode_utf16(&mut [0; 2]) {
+                    out.push_str(&format!("\\u{:04x}", u));
+                }
+            },
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_number;
+
+    fn s(v: &str) -> Value {
+        Value::String(v.to_string())
+    }
+
+    fn n(v: &str) -> Value {
+        Value::Number(Number::from_literal(v))
+    }
+
+    #[test]
+    fn test_parse_to_value() {
+        let data = br#"{
+            "name": "test",
+            "tags": ["a", "b"],
+            "nested": {"x": 1, "y": [true, false, null, {}], "z": []},
+            "pi": 3.14
+        }"#;
+
+        let expected = Value::Object(vec![
+            ("name".into(), s("test")),
+            ("tags".into(), Value::Array(vec![s("a"), s("b")])),
+            ("nested".into(), Value::Object(vec![
+                ("x".into(), n("1")),
+                ("y".into(), Value::Array(vec![
+                    Value::Bool(true),
+                    Value::Bool(false),
+                    Value::Null,
+                    Value::Object(vec![]),
+                ])),
+                ("z".into(), Value::Array(vec![])),
+            ])),
+            ("pi".into(), n("3.14")),
+        ]);
+
+        assert_eq!(expected, parse_to_value(data.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_parse_to_value_scalars() {
+        assert_eq!(s("x"), parse_to_value(br#""x""#.as_slice()).unwrap());
+        assert_eq!(n("-1e5"), parse_to_value(b"-1e5".as_slice()).unwrap());
+        assert_eq!(Value::Null, parse_to_value(b" null ".as_slice()).unwrap());
+        assert_eq!(n("-123456789012345678901234567890"), parse_to_value(b"-123456789012345678901234567890".as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_numbers() {
+        let number = |text: &str| match parse_to_value(text.as_bytes()).unwrap() {
+            Value::Number(n) => n,
+            v => panic!("{:?}", v),
+        };
+        assert_eq!(Number::Int(42), number("42"));
+        assert_eq!(Number::Int(-7), number("-7"));
+        assert_eq!(Number::Int(i64::MIN), number("-9223372036854775808"));
+        assert!(matches!(number("-0"), Number::Float(x) if x == 0.0 && x.is_sign_negative()));
+        assert_eq!("-0.0", number("-0").to_string());
+        assert_eq!(Number::UInt(u64::MAX), number("18446744073709551615"));
+        assert_eq!(Number::Float(2.5), number("2.50"));
+        assert_eq!(Number::Float(-1e-3), number("-1E-3"));
+        assert_eq!(Number::Float(100.0), number("1e2"));
+        let big = "123456789012345678901234567890";
+        assert_eq!(Number::Raw(big.into()), number(big));
+        assert_eq!(Number::Raw("-1e999".into()), number("-1e999"));
+
+        for text in ["42", "-7", "18446744073709551615", "2.5", "-0.001", "1e300", big] {
+            assert_eq!(number(text), number(&number(text).to_string()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_parse_to_value_preserves_order() {
+        let v = parse_to_value(br#"{"b": 1, "a": 2, "b": 3}"#.as_slice()).unwrap();
+        let keys: Vec<_> = match &v {
+            Value::Object(members) => members.iter().map(|(k, _)| k.as_str()).collect(),
+            _ => panic!("not an object"),
+        };
+        assert_eq!(vec!["b", "a", "b"], keys);
+    }
+
+    #[test]
+    fn test_parse_to_value_errors() {
+        assert!(parse_to_value(b"[1, 2".as_slice()).is_err());
+        assert!(matches!(parse_to_value(b"".as_slice()), Err(JsonError::UnexpectedEof { .. })));
+    }
+
+    #[test]
+    fn test_pretty_string() {
+        let v = parse_to_value(br#"{"a": [1, {"b": null}], "c": {}, "d": [], "e": "x"}"#.as_slice()).unwrap();
+        let expected = r#"{
+  "a": [
+    1,
+    {
+      "b": null
+    }
+  ],
+  "c": {},
+  "d": [],
+  "e": "x"
+}"#;
+        assert_eq!(expected, to_pretty_string(&v, 2));
+        assert_eq!("[\n\ttrue\n]".replace('\t', "    "), to_pretty_string(&Value::Array(vec![Value::Bool(true)]), 4));
+        assert_eq!("-1500.0", to_pretty_string(&n("-1.5e3"), 2));
+        assert_eq!("1e300", to_pretty_string(&n("1E+300"), 2));
+    }
+
+    #[test]
+    fn test_pretty_string_escapes() {
+        let v = s("quote\" backslash\\ slash/ \u{8}\u{c}\n\r\t \u{1} \u{1f} caf\u{e9} \u{1f600}");
+        let text = to_pretty_string(&v, 2);
+        assert_eq!(r#""quote\" backslash\\ slash/ \b\f\n\r\t \u0001 \u001f café 😀""#, text);
+        assert_eq!(v, parse_to_value(text.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_pretty_string_round_trip() {
+        let data = r#"{"name": "a\u0000b", "list": [1, -2.5e-3, true, false, null, [[]], {"k e y": {"x": "😀"}}], "": 0}"#;
+        let v = parse_to_value(data.as_bytes()).unwrap();
+        for indent in [0, 2, 4] {
+            let text = to_pretty_string(&v, indent);
+            assert_eq!(v, parse_to_value(text.as_bytes()).unwrap(), "{}", text);
+            assert_eq!(text, to_pretty_string(&parse_to_value(text.as_bytes()).unwrap(), indent));
+        }
+    }
+
+    #[test]
+    fn test_compact_string() {
+        let data = r#" { "a" : [ 1 , { "b" : null } ] ,
+            "c" : { } , "d" : [ ] , "e" : "x y" } "#;
+        let v = parse_to_value(data.as_bytes()).unwrap();
+        assert_eq!(r#"{"a":[1,{"b":null}],"c":{},"d":[],"e":"x y"}"#, to_compact_string(&v));
+        assert_eq!(v, parse_to_value(to_compact_string(&v).as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_compact_string_escapes() {
+        let v = s("\"\\/\u{8}\u{c}\n\r\t\u{1}\u{7f} caf\u{e9} \u{1f600}");
+        let text = to_compact_string(&v);
+        assert_eq!(r#""\"\\/\b\f\n\r\t\u0001\u007f caf\u00e9 \ud83d\ude00""#, text);
+        assert_eq!(v, parse_to_value(text.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_compact_string_numbers() {
+        let compact = |text: &str| to_compact_string(&n(text));
+        assert_eq!("1.5", compact("1.50"));
+        assert_eq!("1", compact("1.000"));
+        assert_eq!("0", compact("0.0"));
+        assert_eq!("-250000000", compact("-2.50E+08"));
+        assert_eq!("1e-7", compact("1e-007"));
+        assert_eq!("0.001", compact("1e-003"));
+        assert_eq!("7", compact("7e0"));
+        assert_eq!("10", compact("10"));
+        assert_eq!("-0", compact("-0"));
+        assert_eq!("1e400", compact("1.000e+400"));
+        assert_eq!("123456789012345678901234567890", compact("123456789012345678901234567890"));
+
+        for text in ["1.50", "2.50E+08", "1e-003", "120", "0.001"] {
+            assert_eq!(parse_number(text), parse_number(&compact(text)));
+        }
+    }
+}
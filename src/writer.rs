@@ -0,0 +1,258 @@
+use std::io;
+
+use crate::value::write_string;
+use crate::{check_number, Event, JsonType};
+
+/// An open array or object.
+struct Level {
+    object: bool,
+    /// Elements or members written so far.
+    count: usize,
+}
+
+/// Writes a JSON document piece by piece, adding the commas and colons.
+///
+/// Calls that would not produce well-formed JSON, such as a value inside
+/// an object without a key, fail with `io::ErrorKind::InvalidInput`. The
+/// output has no whitespace.
+pub struct JsonWriter<W: io::Write> {
+    out: W,
+    stack: Vec<Level>,
+    /// Set after a key until its value is written.
+    after_key: bool,
+    /// Set once the top-level value is complete.
+    done: bool,
+    buf: String,
+}
+
+fn misuse(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+impl<W: io::Write> JsonWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, stack: Vec::new(), after_key: false, done: false, buf: String::new() }
+    }
+
+    pub fn begin_object(&mut self) -> io::Result<()> {
+        self.begin_value()?;
+        self.stack.push(Level { object: true, count: 0 });
+        self.out.write_all(b"{")
+    }
+
+    pub fn end_object(&mut self) -> io::Result<()> {
+        self.end(true)?;
+        self.out.write_all(b"}")
+    }
+
+    pub fn begin_array(&mut self) -> io::Result<()> {
+        self.begin_value()?;
+        self.stack.push(Level { object: false, count: 0 });
+        self.out.write_all(b"[")
+    }
+
+    pub fn end_array(&mut self) -> io::Result<()> {
+        self.end(false)?;
+        self.out.write_all(b"]")
+    }
+
+    /// Writes the key of the next member of the innermost object.
+    pub fn key(&mut self, key: &str) -> io::Result<()> {
+        match self.stack.last_mut() {
+            Some(level) if level.object && !self.after_key => {
+                if level.count > 0 {
+                    self.out.write_all(b",")?;
+                }
+                level.count += 1;
+            },
+            Some(level) if level.object => return Err(misuse("expected a value after the key")),
+            _ => return Err(misuse("keys can only be written in an object")),
+        }
+        self.after_key = true;
+        self.write_string(key)?;
+        self.out.write_all(b":")
+    }
+
+    pub fn string_value(&mut self, s: &str) -> io::Result<()> {
+        self.begin_value()?;
+        self.write_string(s)?;
+        self.end_value();
+        Ok(())
+    }
+
+    /// Writes `n` as is, after checking that it is a valid JSON number.
+    pub fn number_value(&mut self, n: &str) -> io::Result<()> {
+        check_number(n).map_err(|msg| misuse(&msg))?;
+        self.begin_value()?;
+        self.out.write_all(n.as_bytes())?;
+        self.end_value();
+        Ok(())
+    }
+
+    pub fn bool_value(&mut self, b: bool) -> io::Result<()> {
+        self.begin_value()?;
+        self.out.write_all(if b { b"true" } else { b"false" })?;
+        self.end_value();
+        Ok(())
+    }
+
+    pub fn null_value(&mut self) -> io::Result<()> {
+        self.begin_value()?;
+        self.out.write_all(b"null")?;
+        self.end_value();
+        Ok(())
+    }
+
+    /// Writes a value reported by a parser with container events enabled,
+    /// along with its key.
+    pub fn event(&mut self, e: &Event) -> io::Result<()> {
+        if let Some(key) = e.key.filter(|_| !matches!(e.typ, JsonType::ArrayEnd | JsonType::ObjectEnd)) {
+            self.key(key)?;
+        }
+        match e.typ {
+            JsonType::String => self.string_value(e.value),
+            JsonType::Integer | JsonType::Number => self.number_value(e.value),
+            JsonType::True => self.bool_value(true),
+            JsonType::False => self.bool_value(false),
+            JsonType::Null => self.null_value(),
+            JsonType::ArrayStart => self.begin_array(),
+            JsonType::ArrayEnd => self.end_array(),
+            JsonType::ObjectStart => self.begin_object(),
+            JsonType::ObjectEnd => self.end_object(),
+            JsonType::Array | JsonType::Object => {
+                Err(misuse("containers can only be written from container events"))
+            },
+        }
+    }
+
+    /// Checks that the document is complete and returns the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.done {
+            return Err(misuse("the document is incomplete"));
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn begin_value(&mut self) -> io::Result<()> {
+        if self.after_key {
+            self.after_key = false;
+            return Ok(());
+        }
+        match self.stack.last_mut() {
+            Some(level) if level.object => Err(misuse("expected a key before the value")),
+            Some(level) => {
+                level.count += 1;
+                if level.count > 1 {
+                    self.out.write_all(b",")?;
+                }
+                Ok(())
+            },
+            None if self.done => Err(misuse("the top-level value is already written")),
+            None => Ok(()),
+        }
+    }
+
+    fn end_value(&mut self) {
+        self.done = self.stack.is_empty();
+    }
+
+    fn end(&mut self, object: bool) -> io::Result<()> {
+        match self.stack.last() {
+            Some(level) if level.object == object && !self.after_key => {
+                self.stack.pop();
+                self.end_value();
+                Ok(())
+            },
+            Some(level) if level.object == object => Err(misuse("expected a value after the key")),
+            _ if object => Err(misuse("no object to end")),
+            _ => Err(misuse("no array to end")),
+        }
+    }
+
+    fn write_string(&mut self, s: &str) -> io::Result<()> {
+        self.buf.clear();
+        write_string(&mut self.buf, s, false);
+        self.out.write_all(self.buf.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use super::*;
+    use crate::{parse_to_value, JsonParserBuilder, Value};
+
+    fn written(f: impl FnOnce(&mut JsonWriter<Vec<u8>>) -> io::Result<()>) -> io::Result<String> {
+        let mut w = JsonWriter::new(Vec::new());
+        f(&mut w)?;
+        Ok(String::from_utf8(w.finish()?).unwrap())
+    }
+
+    #[test]
+    fn test_writer() {
+        let out = written(|w| {
+            w.begin_object()?;
+            w.key("name")?;
+            w.string_value("a \"quoted\"\n\u{e9}")?;
+            w.key("list")?;
+            w.begin_array()?;
+            w.number_value("1")?;
+            w.number_value("-2.5e3")?;
+            w.begin_object()?;
+            w.end_object()?;
+            w.begin_array()?;
+            w.end_array()?;
+            w.end_array()?;
+            w.key("flags")?;
+            w.begin_array()?;
+            w.bool_value(true)?;
+            w.bool_value(false)?;
+            w.null_value()?;
+            w.end_array()?;
+            w.end_object()
+        }).unwrap();
+        assert_eq!(r#"{"name":"a \"quoted\"\né","list":[1,-2.5e3,{},[]],"flags":[true,false,null]}"#, out);
+
+        let value = parse_to_value(out.as_bytes()).unwrap();
+        let Value::Object(members) = value else { panic!("{:?}", value) };
+        assert_eq!(("name".to_string(), Value::String("a \"quoted\"\n\u{e9}".into())), members[0]);
+        assert_eq!(3, members.len());
+
+        assert_eq!("\"x\"", written(|w| w.string_value("x")).unwrap());
+        assert_eq!("0", written(|w| w.number_value("0")).unwrap());
+    }
+
+    #[test]
+    fn test_writer_events() {
+        let data = br#" {"a": [1, 2.50, {"b c": null}], "\u00e9": "x\ty", "e": [], "f": {"g": true}} "#;
+        let mut w = JsonWriter::new(Vec::new());
+        JsonParserBuilder::new()
+            .container_events(true)
+            .build(data.as_slice(), |e: &Event| {
+                w.event(e).unwrap();
+                ControlFlow::Continue(())
+            })
+            .parse()
+            .unwrap();
+        let out = String::from_utf8(w.finish().unwrap()).unwrap();
+        assert_eq!(r#"{"a":[1,2.50,{"b c":null}],"é":"x\ty","e":[],"f":{"g":true}}"#, out);
+        assert_eq!(parse_to_value(data.as_slice()).unwrap(), parse_to_value(out.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_writer_misuse() {
+        let invalid = |r: io::Result<String>| r.unwrap_err().kind() == io::ErrorKind::InvalidInput;
+        assert!(invalid(written(|w| w.key("a"))));
+        assert!(invalid(written(|w| { w.begin_object()?; w.null_value() })));
+        assert!(invalid(written(|w| { w.begin_object()?; w.key("a")?; w.key("b") })));
+        assert!(invalid(written(|w| { w.begin_object()?; w.key("a")?; w.end_object() })));
+        assert!(invalid(written(|w| { w.begin_array()?; w.end_object() })));
+        assert!(invalid(written(|w| w.end_array())));
+        assert!(invalid(written(|w| { w.null_value()?; w.null_value() })));
+        assert!(invalid(written(|w| w.number_value("01"))));
+        assert!(invalid(written(|w| w.begin_array())));
+        assert!(invalid(written(|_| Ok(()))));
+    }
+}